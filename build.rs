@@ -1,66 +1,740 @@
 use std::env;
+use std::path::{Path, PathBuf};
+
+#[path = "build/sources.rs"]
+mod sources;
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
 fn main() -> Result<()> {
+    // Set by `build_layout_checks`.
+    println!("cargo:rustc-check-cfg=cfg(hdfs_sys_layout_checks)");
+
     // Ignore link while building docs.
     if env::var("DOCS_RS").is_ok() {
+        set_linked_version(hdfs_version());
+        return Ok(());
+    }
+
+    println!("cargo:rerun-if-env-changed=HDFS_SYS_VERBOSE");
+
+    // libhdfs++ is a native client, no JVM is needed.
+    //
+    // The vendored sources always need a JVM, so `vendored` wins if both enabled.
+    if cfg!(feature = "no-jvm") && cfg!(feature = "vendored") {
+        println!("cargo:warning=Feature `no-jvm` is ignored since `vendored` is enabled.");
+    } else if cfg!(feature = "no-jvm") {
+        let Some(lib_dir) = find_libhdfs()? else {
+            return Err("feature `no-jvm` requires a system libhdfs++, \
+            please set `HDFS_LIB_DIR` or `HADOOP_HOME`"
+                .into());
+        };
+        if validate_only() {
+            return report_validation("not needed", Some(&lib_dir));
+        }
+        set_linked_version("system");
+        link_static_cxx();
+        link_atomic();
+        link_sanitizer();
+        emit_header(Some(&lib_dir))?;
         return Ok(());
     }
 
-    find_jvm()?;
+    let jvm_path = find_jvm()?;
 
-    let found = if cfg!(feature = "vendored") {
-        false
+    let lib_dir = if cfg!(feature = "vendored") {
+        None
     } else {
         find_libhdfs()?
     };
-    if !found {
+    if validate_only() {
+        let jvm_path = jvm_path.as_deref().unwrap_or("libnativehelper");
+        return report_validation(jvm_path, lib_dir.as_deref());
+    }
+    if lib_dir.is_some() {
+        set_linked_version("system");
+    } else {
         build_libhdfs()?;
+        build_layout_checks()?;
+    }
+
+    link_static_cxx();
+    link_atomic();
+    link_sanitizer();
+    emit_header(lib_dir.as_deref())?;
+
+    Ok(())
+}
+
+/// Bundle the C++ runtime if `HDFS_STATIC_CXX` is set to anything but `0`
+/// or empty.
+///
+/// A static `libhdfs` built with C++ toolchains may need `libstdc++` at link
+/// time, which breaks fully static binaries.
+fn link_static_cxx() {
+    println!("cargo:rerun-if-env-changed=HDFS_STATIC_CXX");
+
+    if !env::var("HDFS_STATIC_CXX").is_ok_and(|v| v != "0" && !v.is_empty()) {
+        return;
+    }
+    // MSVC links its C++ runtime via `static_crt` instead.
+    if target_os() == "windows" {
+        return;
+    }
+    println!("cargo:rustc-link-arg=-static-libstdc++");
+    println!("cargo:rustc-link-arg=-static-libgcc");
+}
+
+/// Link `libatomic` on targets without native atomics of every size.
+///
+/// gcc for 32-bit ARM linux lowers some atomic builtins, like 64-bit ones
+/// on `armv7-unknown-linux-gnueabihf`, into `__atomic_*` calls provided by
+/// `libatomic`, which is not linked by default. `HDFS_LINK_ATOMIC=1` links
+/// it on any target, `HDFS_LINK_ATOMIC=0` never does.
+fn link_atomic() {
+    println!("cargo:rerun-if-env-changed=HDFS_LINK_ATOMIC");
+
+    let enabled = match env::var("HDFS_LINK_ATOMIC") {
+        Ok(v) => v != "0",
+        Err(_) => {
+            target_os() == "linux"
+                && env::var("CARGO_CFG_TARGET_ARCH").is_ok_and(|arch| arch == "arm")
+        }
+    };
+    if enabled {
+        println!("cargo:rustc-link-lib=atomic");
+        verbose("link libatomic");
+    }
+}
+
+/// Whether to build the C sources with AddressSanitizer.
+///
+/// Enabled by the `asan` feature or `HDFS_SANITIZE=address`.
+fn asan() -> bool {
+    println!("cargo:rerun-if-env-changed=HDFS_SANITIZE");
+
+    cfg!(feature = "asan") || env::var("HDFS_SANITIZE").is_ok_and(|v| v == "address")
+}
+
+/// Add AddressSanitizer flags to `builder` if [`asan`] is enabled.
+fn sanitize(builder: &mut cc::Build) {
+    if asan() {
+        builder.flag("-fsanitize=address");
+        builder.flag("-fno-omit-frame-pointer");
+    }
+}
+
+/// Pass `-fsanitize=address` to the linker if [`asan`] is enabled.
+///
+/// rustc links with `-nodefaultlibs`, so the ASan runtime itself comes from
+/// building the rust code with `-Zsanitizer=address`, without it linking
+/// fails with undefined `__asan_*` symbols.
+fn link_sanitizer() {
+    if asan() {
+        println!("cargo:rustc-link-arg=-fsanitize=address");
+        verbose("build with AddressSanitizer");
+    }
+}
+
+/// Choose the version of vendored sources to build.
+fn vendored_version() -> &'static str {
+    // Since 2.6, hdfs supports windows.
+    //
+    // We build with src from `hdfs_2_6` but expose earlier ABI like `hdfs_2_2`.
+    // This simple trick makes hdfs-sys works on windows without breaking our ABI promise.
+    if target_os() == "windows" && !cfg!(feature = "hdfs_2_6") {
+        return "hdfs_2_6";
+    }
+    hdfs_version()
+}
+
+/// Whether to build position independent code, on unless `HDFS_PIC=0`.
+fn pic() -> bool {
+    println!("cargo:rerun-if-env-changed=HDFS_PIC");
+
+    env::var("HDFS_PIC").map_or(true, |v| v != "0")
+}
+
+/// Whether to build the vendored mutexes with error checking, for the
+/// `debug-mutexes` feature.
+fn debug_mutexes(windows: bool) -> bool {
+    if !cfg!(feature = "debug-mutexes") {
+        return false;
+    }
+    if windows {
+        println!("cargo:warning=Feature `debug-mutexes` is ignored on windows, critical sections have no error checking.");
+        return false;
+    }
+    true
+}
+
+/// Build with link time optimization for the `lto` feature.
+///
+/// Objects are fat, carrying both the LTO bytecode and regular code, so
+/// the archive still links with linkers that can't run LTO. The code is
+/// only optimized across files if the final link passes `-flto` too.
+fn lto(builder: &mut cc::Build, windows: bool) {
+    if !cfg!(feature = "lto") {
+        return;
+    }
+    if windows {
+        println!("cargo:warning=Feature `lto` is ignored on windows.");
+        return;
+    }
+    builder.flag("-flto");
+    builder.flag_if_supported("-ffat-lto-objects");
+    verbose("build with -flto");
+}
+
+/// The `-std=` of the vendored build, `HDFS_CXX_STD` or `c++17` by default.
+///
+/// The vendored sources are C, so the C++ standard only matters with
+/// toolchains compiling them as C++, and is dropped if not supported.
+fn cxx_std() -> Result<String> {
+    println!("cargo:rerun-if-env-changed=HDFS_CXX_STD");
+
+    let std = match env::var("HDFS_CXX_STD") {
+        Ok(std) if !std.is_empty() => std,
+        _ => return Ok("c++17".to_string()),
+    };
+    let valid = ["c++", "gnu++"].iter().any(|prefix| {
+        std.strip_prefix(prefix)
+            .is_some_and(|v| !v.is_empty() && v.chars().all(|c| c.is_ascii_alphanumeric()))
+    });
+    if !valid {
+        return Err(format!("invalid HDFS_CXX_STD {std}, expected like c++14 or gnu++20").into());
+    }
+    verbose(format_args!("build with -std={std}"));
+    Ok(std)
+}
+
+/// Whether `HDFS_VALIDATE_ONLY=1` is set to only run discovery.
+fn validate_only() -> bool {
+    println!("cargo:rerun-if-env-changed=HDFS_VALIDATE_ONLY");
+
+    env::var("HDFS_VALIDATE_ONLY").is_ok_and(|v| v == "1")
+}
+
+/// Print what discovery found for `HDFS_VALIDATE_ONLY=1` without compiling.
+///
+/// The crate built this way links nothing and is not usable.
+fn report_validation(jvm_path: &str, lib_dir: Option<&str>) -> Result<()> {
+    let libhdfs = match lib_dir {
+        Some(lib_dir) => {
+            set_linked_version("system");
+            format!("system libhdfs in {lib_dir}")
+        }
+        None => {
+            // Building from source needs JNI headers.
+            let java_home = java_home()?;
+            if !Path::new(&java_home).join("include/jni.h").exists() {
+                return Err(format!("jni.h is not found in {java_home}/include").into());
+            }
+            let version = vendored_version();
+            set_linked_version(version);
+            format!("build from libhdfs/{version} with JDK {java_home}")
+        }
+    };
+
+    println!("cargo:warning=hdfs-sys: validate only, nothing is compiled or usable");
+    println!("cargo:warning=hdfs-sys: jvm: {jvm_path}");
+    println!("cargo:warning=hdfs-sys: libhdfs: {libhdfs}");
+    Ok(())
+}
+
+/// Expose the linked libhdfs version as `HDFS_SYS_LINKED_VERSION`.
+///
+/// Accepts a version feature like `hdfs_3_3`, which is exposed as `3.3`.
+fn set_linked_version(version: &str) {
+    let version = version.trim_start_matches("hdfs_").replace('_', ".");
+    println!("cargo:rustc-env=HDFS_SYS_LINKED_VERSION={version}");
+}
+
+/// Choose the latest hdfs version enabled by features.
+fn hdfs_version() -> &'static str {
+    let mut version = "hdfs_2_2";
+    if cfg!(feature = "hdfs_2_3") {
+        version = "hdfs_2_3"
+    }
+    if cfg!(feature = "hdfs_2_4") {
+        version = "hdfs_2_4"
+    }
+    if cfg!(feature = "hdfs_2_5") {
+        version = "hdfs_2_5"
+    }
+    if cfg!(feature = "hdfs_2_6") {
+        version = "hdfs_2_6"
+    }
+    if cfg!(feature = "hdfs_2_7") {
+        version = "hdfs_2_7"
+    }
+    if cfg!(feature = "hdfs_2_8") {
+        version = "hdfs_2_8"
+    }
+    if cfg!(feature = "hdfs_2_9") {
+        version = "hdfs_2_9"
+    }
+    if cfg!(feature = "hdfs_2_10") {
+        version = "hdfs_2_10"
+    }
+    if cfg!(feature = "hdfs_3_0") {
+        version = "hdfs_3_0"
+    }
+    if cfg!(feature = "hdfs_3_1") {
+        version = "hdfs_3_1"
+    }
+    if cfg!(feature = "hdfs_3_2") {
+        version = "hdfs_3_2"
     }
+    if cfg!(feature = "hdfs_3_3") {
+        version = "hdfs_3_3"
+    }
+    version
+}
 
+/// Build the C shim that reports struct layouts of the vendored `hdfs.h`.
+///
+/// It's only used by `src/layout_checks.rs`, the linker will drop it elsewhere.
+/// So it's only built along with `libhdfs` from source, which needs the C
+/// compiler and the vendored tree anyway, and enables the tests with the
+/// `hdfs_sys_layout_checks` cfg. Builds linking a system `libhdfs` skip
+/// the checks.
+fn build_layout_checks() -> Result<()> {
+    let version = hdfs_version();
+    let root = sources_dir(version)?;
+    let root = root.display();
+
+    let mut builder = cc::Build::new();
+    builder.warnings(false);
+    builder.flag_if_supported("-w");
+
+    // Since 2.8, `hdfs.h` has been moved to `include/hdfs/hdfs.h`
+    if cfg!(feature = "hdfs_2_8") {
+        builder.include(format!("{root}/libhdfs/{version}/include/hdfs"));
+    } else {
+        builder.include(format!("{root}/libhdfs/{version}"));
+    }
+    if cfg!(feature = "hdfs_2_3") {
+        builder.define("HDFS_SYS_ZERO_COPY", None);
+    }
+    if cfg!(feature = "hdfs_3_0") {
+        builder.define("HDFS_SYS_HEDGED_READ", None);
+    }
+
+    sanitize(&mut builder);
+
+    println!("cargo:rerun-if-changed=src/layout_checks.c");
+    builder.file("src/layout_checks.c");
+    builder.compile("hdfs_sys_layout_checks");
+    println!("cargo:rustc-cfg=hdfs_sys_layout_checks");
+    Ok(())
+}
+
+/// Expose the absolute path of the `hdfs.h` built against to dependents as
+/// `DEP_HDFS_HEADER`, like for running bindgen on the same header.
+///
+/// For a system libhdfs in `lib_dir`, it's the `hdfs.h` or `hdfs/hdfs.h`
+/// in the `include` dir of its installation, like `${HADOOP_HOME}/include`.
+/// If there is none, or `libhdfs` is compiled from source, it's the
+/// vendored header of the version the bindings are for. A system `libhdfs`
+/// without its own header in a checkout without `libhdfs/` skips it with a
+/// warning.
+fn emit_header(lib_dir: Option<&str>) -> Result<()> {
+    let system = lib_dir.and_then(|lib_dir| {
+        // `lib/native` of hadoop, or `lib` and `lib64` of conda and CDP.
+        ["../../include", "../include"]
+            .iter()
+            .flat_map(|include| {
+                ["hdfs.h", "hdfs/hdfs.h"].map(|h| Path::new(lib_dir).join(include).join(h))
+            })
+            .find(|path| path.exists())
+    });
+    let header = match system {
+        Some(header) => header,
+        // Nothing is compiled for a system `libhdfs`, so a partial checkout
+        // without `libhdfs/` must still build, just without the header.
+        None if lib_dir.is_some() => {
+            let version = hdfs_version();
+            let header = PathBuf::from(sources::header(version));
+            if !header.exists() {
+                println!(
+                    "cargo:warning=No hdfs.h found next to the system libhdfs \
+                    and libhdfs/{version} is missing, DEP_HDFS_HEADER is not set."
+                );
+                return Ok(());
+            }
+            header
+        }
+        None => {
+            let version = vendored_version();
+            sources_dir(version)?.join(sources::header(version))
+        }
+    };
+    let header = header.canonicalize()?;
+
+    println!("cargo:header={}", header.display());
+    verbose(format_args!("hdfs.h: {}", header.display()));
     Ok(())
 }
 
-fn find_jvm() -> Result<()> {
-    let jvm_path = java_locator::locate_jvm_dyn_library()?;
+/// Published crate with the same `libhdfs` and `libdirent` trees, used by
+/// `fetch-sources`.
+///
+/// Bump both together only when the vendored sources changed.
+#[cfg(feature = "fetch-sources")]
+const SOURCES_URL: &str = "https://static.crates.io/crates/hdfs-sys/hdfs-sys-0.3.0.crate";
+#[cfg(feature = "fetch-sources")]
+const SOURCES_SHA256: &str = "33e2d5cefba2d51a26b44d2a493f963a32725a0f6593c91be4a610ad449c49cb";
+
+/// Get the dir containing `libhdfs` and `libdirent` for `version`.
+///
+/// It's the crate itself, or the downloaded sources if `libhdfs/{version}`
+/// is missing and `fetch-sources` is enabled.
+fn sources_dir(version: &str) -> Result<PathBuf> {
+    if Path::new("libhdfs").join(version).exists() {
+        return Ok(PathBuf::from("."));
+    }
+    #[cfg(feature = "fetch-sources")]
+    return fetch_sources(version);
+    #[cfg(not(feature = "fetch-sources"))]
+    Err(format!(
+        "libhdfs/{version} is missing, please check out the full repo \
+        or enable the `fetch-sources` feature"
+    )
+    .into())
+}
+
+/// Download and unpack [`SOURCES_URL`] into `OUT_DIR` with `curl` and `tar`.
+#[cfg(feature = "fetch-sources")]
+fn fetch_sources(version: &str) -> Result<PathBuf> {
+    use sha2::{Digest, Sha256};
+    use std::process::Command;
+
+    let out_dir = PathBuf::from(env::var("OUT_DIR")?);
+    let root = out_dir.join("hdfs-sys-0.3.0");
+    if root.join("libhdfs").join(version).exists() {
+        return Ok(root);
+    }
+
+    let archive = out_dir.join("hdfs-sys-sources.crate");
+    verbose(format_args!("fetching libhdfs sources from {SOURCES_URL}"));
+    let status = Command::new("curl")
+        .args(["-sSfL", "-o"])
+        .arg(&archive)
+        .arg(SOURCES_URL)
+        .status()
+        .map_err(|err| format!("failed to run curl to fetch sources: {err}"))?;
+    if !status.success() {
+        return Err(format!("fetch {SOURCES_URL} failed: {status}").into());
+    }
+
+    let sha256: String = Sha256::digest(std::fs::read(&archive)?)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect();
+    if sha256 != SOURCES_SHA256 {
+        std::fs::remove_file(&archive)?;
+        return Err(format!(
+            "checksum mismatch of {SOURCES_URL}, expected {SOURCES_SHA256}, got {sha256}"
+        )
+        .into());
+    }
+
+    let status = Command::new("tar")
+        .arg("-xzf")
+        .arg(&archive)
+        .arg("-C")
+        .arg(&out_dir)
+        .status()
+        .map_err(|err| format!("failed to run tar to unpack sources: {err}"))?;
+    if !status.success() {
+        return Err(format!("unpack {} failed: {status}", archive.display()).into());
+    }
+    if !root.join("libhdfs").join(version).exists() {
+        return Err(format!("libhdfs/{version} is missing in {SOURCES_URL}").into());
+    }
+    Ok(root)
+}
+
+/// Find and link `libjvm`, returning the dir of it.
+///
+/// Returns `None` if no `libjvm` is linked, like `libnativehelper` on android.
+fn find_jvm() -> Result<Option<String>> {
+    let jvm_path = if cfg!(feature = "bundled-jdk") {
+        bundled_jvm_path()?
+    } else {
+        locate_jvm_path()?
+    };
+    let Some(jvm_path) = jvm_path else {
+        return Ok(None);
+    };
 
     println!("cargo:rustc-link-lib=jvm");
     println!("cargo:rustc-link-search=native={jvm_path}");
-
-    // Add JVM to rpath
-    println!("cargo:rustc-link-arg=-Wl,-rpath,{jvm_path}");
+    verbose(format_args!("link jvm in {jvm_path}"));
+
+    // Add JVM to rpath, MSVC has no rpath.
+    println!("cargo:rerun-if-env-changed=HDFS_NO_JVM_RPATH");
+    if env::var("HDFS_NO_JVM_RPATH").is_ok_and(|v| v == "1") {
+        verbose("skip rpath of jvm for HDFS_NO_JVM_RPATH");
+    } else if target_os() != "windows" {
+        println!("cargo:rustc-link-arg=-Wl,-rpath,{jvm_path}");
+    }
 
     // Export the used JVM_PATH as metadata, in case a crate needs it in order to link
     println!("cargo:metadata=JVM_PATH={jvm_path}");
+    // For `safe::add_jvm_to_dll_path`.
+    println!("cargo:rustc-env=HDFS_SYS_JVM_PATH={jvm_path}");
 
     // Add jvm.lib into search path for windows.
-    #[cfg(windows)]
-    if let Ok(jvm_lib_path) = java_locator::locate_file("jvm.lib") {
-        println!("cargo:rustc-link-search=native={jvm_lib_path}");
+    if target_os() == "windows" {
+        if cfg!(feature = "bundled-jdk") {
+            println!("cargo:rustc-link-search=native={}/lib", bundled_jdk()?);
+        } else if let Ok(jvm_lib_path) = java_locator::locate_file("jvm.lib") {
+            println!("cargo:rustc-link-search=native={jvm_lib_path}");
+        }
     }
 
-    Ok(())
+    Ok(Some(jvm_path))
+}
+
+/// Locate the dir of `libjvm` with `java_locator`.
+///
+/// Returns `None` if no JVM is needed to be linked.
+fn locate_jvm_path() -> Result<Option<String>> {
+    find_conda_jdk();
+    find_alternatives_jdk();
+
+    match java_locator::locate_jvm_dyn_library() {
+        Ok(jvm_path) => Ok(Some(verify_jvm_path(&jvm_path)?)),
+        // ART doesn't ship a `libjvm` unless a JVM is bundled under `JAVA_HOME`,
+        // the JNI invocation API is provided by `libnativehelper` instead.
+        Err(_) if target_os() == "android" => {
+            println!("cargo:rustc-link-lib=nativehelper");
+            verbose("link nativehelper for android");
+            Ok(None)
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Get the pinned JDK set by `HDFS_BUNDLED_JDK` for feature `bundled-jdk`.
+fn bundled_jdk() -> Result<String> {
+    println!("cargo:rerun-if-env-changed=HDFS_BUNDLED_JDK");
+
+    match env::var("HDFS_BUNDLED_JDK") {
+        Ok(jdk) if !jdk.is_empty() => Ok(jdk),
+        _ => {
+            Err("feature `bundled-jdk` requires `HDFS_BUNDLED_JDK` set to the path of a JDK".into())
+        }
+    }
+}
+
+/// Find the dir of `libjvm` in the pinned JDK without `java_locator`.
+///
+/// JDK 9 and later keep `libjvm` in `lib/server` (`bin/server` on windows),
+/// JDK 8 keeps it in `jre/lib/<arch>/server`.
+fn bundled_jvm_path() -> Result<Option<String>> {
+    let jdk = bundled_jdk()?;
+
+    if target_os() == "windows" {
+        return Ok(Some(verify_jvm_path(&format!("{jdk}/bin"))?));
+    }
+    if let Ok(jvm_path) = verify_jvm_path(&format!("{jdk}/lib")) {
+        return Ok(Some(jvm_path));
+    }
+    let arch = jre_arch();
+    Ok(Some(verify_jvm_path(&format!("{jdk}/jre/lib/{arch}"))?))
+}
+
+/// Get the java home to build libhdfs against.
+fn java_home() -> Result<String> {
+    if cfg!(feature = "bundled-jdk") {
+        return bundled_jdk();
+    }
+    Ok(java_locator::locate_java_home()?)
+}
+
+/// Make sure `libjvm` itself is in `jvm_path`, or its `server` and `client` subdirs.
+///
+/// `java_locator` returns the first dir containing any `libjvm.*`, which is
+/// not always the loadable library and only fails at runtime.
+fn verify_jvm_path(jvm_path: &str) -> Result<String> {
+    let name = match target_os().as_str() {
+        "windows" => "jvm.dll",
+        "macos" => "libjvm.dylib",
+        _ => "libjvm.so",
+    };
+
+    for dir in [
+        jvm_path.to_string(),
+        format!("{jvm_path}/server"),
+        format!("{jvm_path}/client"),
+    ] {
+        if Path::new(&dir).join(name).is_file() {
+            return Ok(dir);
+        }
+    }
+
+    Err(format!(
+        "{name} is not found in {jvm_path} or its server and client subdirs, \
+        please set `JAVA_HOME` to a valid JDK"
+    )
+    .into())
+}
+
+/// Use the JDK installed by conda if `JAVA_HOME` is not set.
+///
+/// conda-forge `openjdk` lives in `$CONDA_PREFIX/lib/jvm` for recent releases
+/// and `$CONDA_PREFIX` for older ones.
+fn find_conda_jdk() {
+    println!("cargo:rerun-if-env-changed=CONDA_PREFIX");
+    println!("cargo:rerun-if-env-changed=JAVA_HOME");
+
+    if env::var_os("JAVA_HOME").is_some_and(|v| !v.is_empty()) {
+        return;
+    }
+    let Some(prefix) = env::var("CONDA_PREFIX").ok().filter(|v| !v.is_empty()) else {
+        return;
+    };
+    for java_home in [format!("{prefix}/lib/jvm"), prefix] {
+        if Path::new(&java_home).join("include/jni.h").exists() {
+            // `java_locator` respects `JAVA_HOME`, set it for the rest of the build.
+            env::set_var("JAVA_HOME", java_home);
+            return;
+        }
+    }
+}
+
+/// Use the JDK of `java` in `PATH` on linux if `JAVA_HOME` is not set.
+///
+/// Debian and Ubuntu manage `java` with `update-alternatives`, a chain of
+/// symlinks like `/usr/bin/java -> /etc/alternatives/java -> .../bin/java`.
+/// Resolving it like `readlink -f $(which java)` gives the real JDK, which
+/// is more reliable than what `java_locator` finds from the symlink.
+fn find_alternatives_jdk() {
+    println!("cargo:rerun-if-env-changed=PATH");
+
+    // `PATH` is of the host, it says nothing about the target if cross compiling.
+    if target_os() != "linux" || env::var("HOST") != env::var("TARGET") {
+        return;
+    }
+    if env::var_os("JAVA_HOME").is_some_and(|v| !v.is_empty()) {
+        return;
+    }
+    let Some(java) = env::var_os("PATH").and_then(|paths| {
+        env::split_paths(&paths)
+            .map(|dir| dir.join("java"))
+            .find(|java| java.is_file())
+    }) else {
+        return;
+    };
+    let Ok(java) = std::fs::canonicalize(java) else {
+        return;
+    };
+
+    // `{home}/bin/java`, which is `{jdk}/jre/bin/java` for JDK 8.
+    let Some(home) = java.parent().and_then(Path::parent) else {
+        return;
+    };
+    let jdk = match home.file_name() {
+        Some(name) if name == "jre" => home.parent().unwrap_or(home),
+        _ => home,
+    };
+    let home = home.display();
+    let found = verify_jvm_path(&format!("{home}/lib"))
+        .or_else(|_| verify_jvm_path(&format!("{home}/lib/{}", jre_arch())));
+    if let Ok(jvm_path) = found {
+        verbose(format_args!(
+            "resolved java in PATH to {}, libjvm in {jvm_path}",
+            jdk.display()
+        ));
+        // `java_locator` respects `JAVA_HOME`, set it for the rest of the build.
+        env::set_var("JAVA_HOME", jdk);
+    }
+}
+
+/// Name of the arch dir in `jre/lib` of JDK 8, like `amd64`.
+fn jre_arch() -> String {
+    match env::var("CARGO_CFG_TARGET_ARCH")
+        .unwrap_or_default()
+        .as_str()
+    {
+        "x86_64" => "amd64".to_string(),
+        arch => arch.to_string(),
+    }
+}
+
+/// Name of libhdfs chosen by the `libname-*` features, `hdfs` by default.
+///
+/// It's used for both the system and the compiled libhdfs. Features are
+/// additive, so `libname-hadoop-hdfs` wins over the default `libname-hdfs`.
+fn lib_name() -> &'static str {
+    if cfg!(feature = "libname-hadoop-hdfs") {
+        "hadoop_hdfs"
+    } else {
+        "hdfs"
+    }
+}
+
+/// Whether `name` is a full file name of the library instead of `-l` name.
+fn is_verbatim(name: &str) -> bool {
+    name.contains('.')
+}
+
+/// Get the os of the target we are building for.
+fn target_os() -> String {
+    env::var("CARGO_CFG_TARGET_OS").unwrap_or_default()
 }
 
 /// Find libhdfs
 ///
-/// Return `true` if libhdfs is found, else `false`.
+/// Return the dir of libhdfs if found, else `None`.
 ///
-/// - Check `HDFS_LIB_DIR` first, then `HADOOP_HOME`.
+/// - Check `HDFS_LIB_DIR` first, then `HADOOP_HOME` as [`hadoop_lib_dirs`],
+///   then the default CDP parcel for `dist-cdp`, then `CONDA_PREFIX`.
 /// - If `HDFS_STATIC` is set, link statically, otherwise, dynamic.
-fn find_libhdfs() -> Result<bool> {
+/// - If `HDFS_LIBHDFS_NAME` is set, link it instead of [`lib_name`].
+fn find_libhdfs() -> Result<Option<String>> {
     // rerun if hdfs related env changed
     println!("cargo:rerun-if-env-changed=HDFS_LIB_DIR");
     println!("cargo:rerun-if-env-changed=HDFS_STATIC");
     println!("cargo:rerun-if-env-changed=HADOOP_HOME");
+    println!("cargo:rerun-if-env-changed=CONDA_PREFIX");
+    println!("cargo:rerun-if-env-changed=HDFS_LIBHDFS_NAME");
+
+    let name = match env::var("HDFS_LIBHDFS_NAME") {
+        Ok(name) if !name.is_empty() => name,
+        // libhdfs++ provides the same C API in `libhdfspp`.
+        _ if cfg!(feature = "no-jvm") && !cfg!(feature = "vendored") => "hdfspp".to_string(),
+        _ => lib_name().to_string(),
+    };
+    let name = name.as_str();
 
     let lib_dir = if let Ok(lib_dir) = env::var("HDFS_LIB_DIR") {
         lib_dir
     } else if let Ok(hadoop_home) = env::var("HADOOP_HOME") {
-        format!("{hadoop_home}/lib/native")
+        let dirs = hadoop_lib_dirs(&hadoop_home);
+        match dirs.iter().find(|dir| has_lib(dir, name)) {
+            Some(dir) => dir.clone(),
+            None => dirs[0].clone(),
+        }
+    } else if let Some(lib_dir) = Some(format!("{CDP_PARCEL}/lib64"))
+        .filter(|_| cfg!(feature = "dist-cdp"))
+        .filter(|lib_dir| has_lib(lib_dir, name))
+    {
+        lib_dir
+    } else if let Some(lib_dir) = env::var("CONDA_PREFIX")
+        .ok()
+        .filter(|prefix| !prefix.is_empty())
+        .map(|prefix| format!("{prefix}/lib"))
+        .filter(|lib_dir| has_lib(lib_dir, name))
+    {
+        // conda-forge `hadoop` installs native libs into `$CONDA_PREFIX/lib`.
+        lib_dir
     } else {
-        return Ok(false);
+        verbose("libhdfs is not found in env");
+        return Ok(None);
     };
 
     println!("cargo:rustc-link-search=native={lib_dir}");
@@ -68,32 +742,77 @@ fn find_libhdfs() -> Result<bool> {
         Some(_) => "static",
         None => "dylib",
     };
-    println!("cargo:rustc-link-lib={mode}=hdfs");
+    // A file name like `libhdfs.so.0` is passed to the linker as is.
+    if is_verbatim(name) {
+        println!("cargo:rustc-link-lib={mode}:+verbatim={name}");
+    } else {
+        println!("cargo:rustc-link-lib={mode}={name}");
+    }
+    verbose(format_args!(
+        "link {mode} {name} in {lib_dir} for {}",
+        hdfs_version()
+    ));
+
+    Ok(Some(lib_dir))
+}
 
-    Ok(true)
+/// The default parcel of CDP, which `HADOOP_HOME` is `lib/hadoop` of.
+const CDP_PARCEL: &str = "/opt/cloudera/parcels/CDH";
+
+/// Dirs to search libhdfs in `HADOOP_HOME`, for the `dist-*` features.
+///
+/// - Apache releases ship it in `lib/native`.
+/// - CDP parcels set `HADOOP_HOME` to `{parcel}/lib/hadoop` and keep
+///   `libhdfs` in `{parcel}/lib64`, some builds still have `lib/native`.
+fn hadoop_lib_dirs(hadoop_home: &str) -> Vec<String> {
+    let mut dirs = vec![format!("{hadoop_home}/lib/native")];
+    if cfg!(feature = "dist-cdp") {
+        dirs.push(format!("{hadoop_home}/../../lib64"));
+    }
+    dirs
+}
+
+/// Check if `dir` contains the static or dynamic library of `name`.
+fn has_lib(dir: &str, name: &str) -> bool {
+    if is_verbatim(name) {
+        return Path::new(dir).join(name).exists();
+    }
+    [
+        format!("lib{name}.so"),
+        format!("lib{name}.dylib"),
+        format!("lib{name}.a"),
+        format!("{name}.lib"),
+    ]
+    .iter()
+    .any(|file| Path::new(dir).join(file).exists())
 }
 
 fn build_libhdfs() -> Result<()> {
-    let java_home = java_locator::locate_java_home()?;
+    let java_home = java_home()?;
 
-    // Static link compiled `libhdfs.a`
-    println!("cargo:rustc-link-lib=static=hdfs");
+    // Check the target instead of `cfg!`, which is the host running build.rs.
+    let windows = target_os() == "windows";
 
     let mut builder = cc::Build::new();
     builder.warnings(false);
 
-    // This flag does not work on windows, just throws warnings
-    #[cfg(not(windows))]
-    builder.static_flag(true);
-
     builder.static_crt(true);
 
+    // Downstream cdylibs embed the static `libhdfs.a`, which fails with
+    // "recompile with -fPIC" unless all objects are position independent.
+    if !windows {
+        builder.pic(pic());
+    }
+
     // Ignore all warnings from cc as we don't care about code written by Apache Hadoop.
     builder.flag_if_supported("-w");
-    builder.flag_if_supported("-std=c++17");
+    builder.flag_if_supported(format!("-std={}", cxx_std()?));
 
     // Inspired by [hadoop-hdfs-native-client/src/CMakeLists.txt](https://github.com/apache/hadoop/blob/trunk/hadoop-hdfs-project/hadoop-hdfs-native-client/src/CMakeLists.txt)
-    if cfg!(windows) {
+    //
+    // These MSVC flags are arch independent, so they work for both
+    // `x86_64-pc-windows-msvc` and `aarch64-pc-windows-msvc`.
+    if windows {
         // Set the optimizer level.
         builder.flag("-O2");
         // Set warning level 4.
@@ -111,121 +830,143 @@ fn build_libhdfs() -> Result<()> {
         // Omit unneeded headers.
         builder.flag("-DWIN32_LEAN_AND_MEAN");
     } else {
-        builder.flag("-fvisibility=hidden");
+        // Sources before 2.7 don't mark the public API as visible, so keep
+        // all symbols for the shared library.
+        if !cfg!(feature = "shared") {
+            builder.flag("-fvisibility=hidden");
+        }
         // using old default behavior on GCC >= 10.0
         builder.flag("-fcommon");
     }
 
     // Handle java headers.
+    //
+    // Check the target os instead of `cfg!`, since android is always cross compiled.
     builder.include(format!("{java_home}/include"));
-    if cfg!(target_os = "linux") {
-        builder.include(format!("{java_home}/include/linux"));
-    }
-    if cfg!(target_os = "macos") {
-        builder.include(format!("{java_home}/include/darwin"));
-    }
-    if cfg!(target_os = "windows") {
-        builder.include(format!("{java_home}/include/win32"));
-    }
+    match target_os().as_str() {
+        // The NDK mirrors the linux layout, and the posix sources build
+        // against bionic without any glibc specific flags.
+        "linux" | "android" => builder.include(format!("{java_home}/include/linux")),
+        "macos" => builder.include(format!("{java_home}/include/darwin")),
+        "windows" => builder.include(format!("{java_home}/include/win32")),
+        _ => &mut builder,
+    };
 
-    // Choose the latest hdfs version.
-    let mut version = "hdfs_2_2";
-    if cfg!(feature = "hdfs_2_3") {
-        version = "hdfs_2_3"
-    }
-    if cfg!(feature = "hdfs_2_4") {
-        version = "hdfs_2_4"
-    }
-    if cfg!(feature = "hdfs_2_5") {
-        version = "hdfs_2_5"
-    }
-    // Since 2.6, hdfs supports windows.
-    //
-    // We build with src from `hdfs_2_6` but expose earlier ABI like `hdfs_2_2`.
-    // This simple trick makes hdfs-sys works on windows without breaking our ABI promise.
-    if cfg!(target_os = "windows") {
-        version = "hdfs_2_6"
-    }
-    if cfg!(feature = "hdfs_2_6") {
-        version = "hdfs_2_6"
-    }
-    if cfg!(feature = "hdfs_2_7") {
-        version = "hdfs_2_7"
-    }
-    if cfg!(feature = "hdfs_2_8") {
-        version = "hdfs_2_8"
-    }
-    if cfg!(feature = "hdfs_2_9") {
-        version = "hdfs_2_9"
-    }
-    if cfg!(feature = "hdfs_2_10") {
-        version = "hdfs_2_10"
-    }
-    if cfg!(feature = "hdfs_3_0") {
-        version = "hdfs_3_0"
-    }
-    if cfg!(feature = "hdfs_3_1") {
-        version = "hdfs_3_1"
+    let version = vendored_version();
+    set_linked_version(version);
+    let root = sources_dir(version)?;
+    let root = root.display();
+
+    let sources = sources::libhdfs_sources(version, windows);
+    for dir in &sources.includes {
+        builder.include(format!("{root}/{dir}"));
     }
-    if cfg!(feature = "hdfs_3_2") {
-        version = "hdfs_3_2"
+    for file in &sources.files {
+        if debug_mutexes(windows) && file.ends_with("os/posix/mutexes.c") {
+            // Includes the vendored file itself.
+            println!("cargo:rerun-if-changed=src/mutexes_debug.c");
+            builder.file("src/mutexes_debug.c");
+            continue;
+        }
+        builder.file(format!("{root}/{file}"));
     }
-    if cfg!(feature = "hdfs_3_3") {
-        version = "hdfs_3_3"
+    // Needs the thread local state of 3.0, see the file for details.
+    if cfg!(feature = "jni-interop") && sources::parse_version(version) >= (3, 0) {
+        println!("cargo:rerun-if-changed=src/thread_detach.c");
+        builder.file("src/thread_detach.c");
     }
 
-    builder.include("libhdfs");
-    builder.include(format!("libhdfs/{version}"));
-    builder.file(format!("libhdfs/{version}/exception.c"));
-    builder.file(format!("libhdfs/{version}/jni_helper.c"));
-    builder.file(format!("libhdfs/{version}/hdfs.c"));
-
-    // Since 2.6, we need to include mutexes.
-    if cfg!(feature = "hdfs_2_6") || cfg!(target_os = "windows") {
-        builder.include(format!("libhdfs/{version}/os"));
-
-        if cfg!(target_os = "windows") {
-            builder.include(format!("libhdfs/{version}/os/windows"));
-            builder.file(format!("libhdfs/{version}/os/windows/mutexes.c"));
-            builder.file(format!("libhdfs/{version}/os/windows/thread.c"));
-            builder.file(format!(
-                "libhdfs/{version}/os/windows/thread_local_storage.c"
-            ));
-        } else {
-            builder.include(format!("libhdfs/{version}/os/posix"));
-            builder.file(format!("libhdfs/{version}/os/posix/mutexes.c"));
-            builder.file(format!("libhdfs/{version}/os/posix/thread.c"));
-            builder.file(format!("libhdfs/{version}/os/posix/thread_local_storage.c"));
-        }
+    #[cfg(not(feature = "vendored"))]
+    {
+        println!("cargo:warning=Building libhdfs from source as a fallback, \
+        if you are encountering issues with missing headers on JDK8, consider enabling the `vendored` feature.");
     }
 
-    // From 2.6 to 3.3, we need to include htable (removed in 3.3)
-    if cfg!(feature = "hdfs_2_6") && !cfg!(feature = "hdfs_3_3") {
-        builder.include(format!("libhdfs/{version}/common"));
-        builder.file(format!("libhdfs/{version}/common/htable.c"));
+    sanitize(&mut builder);
+    lto(&mut builder, windows);
+
+    verbose(format_args!("building libhdfs from libhdfs/{version}"));
+    let args: Vec<_> = builder
+        .get_compiler()
+        .args()
+        .iter()
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect();
+    verbose(format_args!("compiler args: {}", args.join(" ")));
+    for file in builder.get_files() {
+        verbose(format_args!("compile file: {}", file.display()));
     }
 
-    // Since 2.8, `hdfs.h` has been moved to `include/hdfs/hdfs.h`
-    if cfg!(feature = "hdfs_2_8") {
-        builder.include(format!("libhdfs/{version}/include"));
+    if cfg!(feature = "shared") {
+        return build_shared(&builder, windows);
     }
 
-    // Since 3.3, we need to compile `jclasses.c`
-    if cfg!(feature = "hdfs_3_3") {
-        builder.file(format!("libhdfs/{version}/jclasses.c"));
+    // Static link compiled `libhdfs.a`
+    let name = lib_name();
+    println!("cargo:rustc-link-lib=static={name}");
+    builder.compile(name);
+    Ok(())
+}
 
-        // Since 3.3, windows will need to link `dirent`
-        if cfg!(target_os = "windows") {
-            builder.include("libdirent/include");
-        }
+/// Link the compiled objects into a shared `libhdfs` in `OUT_DIR` and link
+/// it dynamically, for the `shared` feature.
+///
+/// `libjvm` is left undefined in `libhdfs`, it's resolved from the final
+/// binary which links `libjvm` as usual.
+fn build_shared(builder: &cc::Build, windows: bool) -> Result<()> {
+    if windows {
+        return Err("feature `shared` is not supported on windows yet".into());
     }
+    let macos = target_os() == "macos";
 
-    #[cfg(not(feature = "vendored"))]
-    {
-        println!("cargo:warning=Building libhdfs from source as a fallback, \
-        if you are encountering issues with missing headers on JDK8, consider enabling the `vendored` feature.");
+    let out_dir = env::var("OUT_DIR")?;
+    let lib = lib_name();
+    let name = if macos {
+        format!("lib{lib}.dylib")
+    } else {
+        format!("lib{lib}.so")
+    };
+    let objects = builder.clone().pic(true).compile_intermediates();
+
+    let mut cmd = builder.get_compiler().to_command();
+    cmd.arg("-shared")
+        .arg("-o")
+        .arg(Path::new(&out_dir).join(&name))
+        .args(&objects);
+    if macos {
+        cmd.arg("-Wl,-undefined,dynamic_lookup")
+            .arg(format!("-Wl,-install_name,@rpath/{name}"));
+    } else {
+        cmd.arg(format!("-Wl,-soname,{name}")).arg("-pthread");
+    }
+    // Not the `Debug` of `cmd`, which has all the env that `cc` set on it.
+    let args: Vec<_> = cmd
+        .get_args()
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect();
+    verbose(format_args!(
+        "linking shared libhdfs: {} {}",
+        cmd.get_program().to_string_lossy(),
+        args.join(" ")
+    ));
+    let status = cmd.status()?;
+    if !status.success() {
+        return Err(format!("link shared {name} failed: {status}").into());
     }
 
-    builder.compile("hdfs");
+    println!("cargo:rustc-link-lib=dylib={lib}");
+    println!("cargo:rustc-link-search=native={out_dir}");
+    println!("cargo:rustc-link-arg=-Wl,-rpath,{out_dir}");
+    // Exposed as `DEP_HDFS_LIB_DIR` to find the library to ship.
+    println!("cargo:lib_dir={out_dir}");
     Ok(())
 }
+
+/// Print build diagnostics if `build-verbose` is enabled or `HDFS_SYS_VERBOSE=1`.
+///
+/// This only prints, the produced artifact is the same.
+fn verbose(msg: impl std::fmt::Display) {
+    if cfg!(feature = "build-verbose") || env::var("HDFS_SYS_VERBOSE").is_ok_and(|v| v == "1") {
+        println!("cargo:warning=hdfs-sys: {msg}");
+    }
+}
@@ -0,0 +1,82 @@
+//! Source files of the vendored `libhdfs`, shared by `build.rs` and the
+//! tests in `src/tests.rs`.
+//!
+//! The file set only depends on the version of the compiled sources, not on
+//! which features are enabled. Features are additive, the latest enabled one
+//! decides the version, so every combination maps to exactly one file set.
+
+/// Include dirs and C files to build one version of `libhdfs`.
+///
+/// All paths are relative to the dir containing `libhdfs` and `libdirent`.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Sources {
+    pub includes: Vec<String>,
+    pub files: Vec<String>,
+}
+
+/// Parse a version like `hdfs_3_3` into `(3, 3)`.
+pub fn parse_version(version: &str) -> (u32, u32) {
+    let parse = |v: &str| -> Option<(u32, u32)> {
+        let (major, minor) = v.strip_prefix("hdfs_")?.split_once('_')?;
+        Some((major.parse().ok()?, minor.parse().ok()?))
+    };
+    parse(version).unwrap_or_else(|| panic!("invalid hdfs version {version}"))
+}
+
+/// Get the sources to build `libhdfs/{version}` for the target.
+pub fn libhdfs_sources(version: &str, windows: bool) -> Sources {
+    let v = parse_version(version);
+    let dir = format!("libhdfs/{version}");
+    let mut src = Sources::default();
+
+    src.includes.push("libhdfs".to_string());
+    src.includes.push(dir.clone());
+    for file in ["exception.c", "jni_helper.c", "hdfs.c"] {
+        src.files.push(format!("{dir}/{file}"));
+    }
+
+    // Since 2.6, we need to include mutexes.
+    if v >= (2, 6) {
+        let os = if windows { "windows" } else { "posix" };
+        src.includes.push(format!("{dir}/os"));
+        src.includes.push(format!("{dir}/os/{os}"));
+        for file in ["mutexes.c", "thread.c", "thread_local_storage.c"] {
+            src.files.push(format!("{dir}/os/{os}/{file}"));
+        }
+    }
+
+    // From 2.6 to 3.3, we need to include htable (removed in 3.3)
+    if ((2, 6)..(3, 3)).contains(&v) {
+        src.includes.push(format!("{dir}/common"));
+        src.files.push(format!("{dir}/common/htable.c"));
+    }
+
+    // Since 2.8, `hdfs.h` has been moved to `include/hdfs/hdfs.h`
+    if v >= (2, 8) {
+        src.includes.push(format!("{dir}/include"));
+    }
+
+    // Since 3.3, we need to compile `jclasses.c`
+    if v >= (3, 3) {
+        src.files.push(format!("{dir}/jclasses.c"));
+
+        // Since 3.3, windows will need to link `dirent`
+        //
+        // `libdirent` is header only on top of win32 APIs, so it builds for any arch.
+        if windows {
+            src.includes.push("libdirent/include".to_string());
+        }
+    }
+
+    src
+}
+
+/// Get the `hdfs.h` of `libhdfs/{version}`, relative like [`Sources`].
+pub fn header(version: &str) -> String {
+    // Since 2.8, `hdfs.h` has been moved to `include/hdfs/hdfs.h`
+    if parse_version(version) >= (2, 8) {
+        format!("libhdfs/{version}/include/hdfs/hdfs.h")
+    } else {
+        format!("libhdfs/{version}/hdfs.h")
+    }
+}
@@ -0,0 +1,87 @@
+/*
+ * Report the layout of structs declared in the vendored hdfs.h.
+ *
+ * Used by `src/layout_checks.rs` to compare with our rust bindings.
+ *
+ * The FILL functions write known values into every field, so that rust can
+ * read them back. The values have distinct bytes, a field with the wrong
+ * width or byte order reads back as a different value.
+ */
+
+#include <stddef.h>
+
+#include "hdfs.h"
+
+#define SIZE_OF(ty) \
+    size_t hdfs_sys_size_of_##ty(void) { return sizeof(ty); }
+
+#define ALIGN_OF(ty) \
+    struct hdfs_sys_align_of_##ty##_t { char c; ty t; }; \
+    size_t hdfs_sys_align_of_##ty(void) { \
+        return offsetof(struct hdfs_sys_align_of_##ty##_t, t); \
+    }
+
+#define OFFSET_OF(ty, field) \
+    size_t hdfs_sys_offset_of_##ty##_##field(void) { return offsetof(ty, field); }
+
+SIZE_OF(hdfsFileInfo)
+ALIGN_OF(hdfsFileInfo)
+OFFSET_OF(hdfsFileInfo, mKind)
+OFFSET_OF(hdfsFileInfo, mName)
+OFFSET_OF(hdfsFileInfo, mLastMod)
+OFFSET_OF(hdfsFileInfo, mSize)
+OFFSET_OF(hdfsFileInfo, mReplication)
+OFFSET_OF(hdfsFileInfo, mBlockSize)
+OFFSET_OF(hdfsFileInfo, mOwner)
+OFFSET_OF(hdfsFileInfo, mGroup)
+OFFSET_OF(hdfsFileInfo, mPermissions)
+OFFSET_OF(hdfsFileInfo, mLastAccess)
+
+void hdfs_sys_fill_hdfsFileInfo(hdfsFileInfo *info) {
+    info->mKind = kObjectKindDirectory;
+    info->mName = "name";
+    info->mLastMod = 0x01020304;
+    info->mSize = 0x0102030405060708;
+    info->mReplication = 0x0102;
+    info->mBlockSize = 0x0807060504030201;
+    info->mOwner = "owner";
+    info->mGroup = "group";
+    info->mPermissions = 0x0201;
+    info->mLastAccess = 0x04030201;
+}
+
+typedef struct hdfsReadStatistics hdfsReadStatistics;
+
+SIZE_OF(hdfsReadStatistics)
+ALIGN_OF(hdfsReadStatistics)
+OFFSET_OF(hdfsReadStatistics, totalBytesRead)
+OFFSET_OF(hdfsReadStatistics, totalLocalBytesRead)
+OFFSET_OF(hdfsReadStatistics, totalShortCircuitBytesRead)
+#ifdef HDFS_SYS_ZERO_COPY
+OFFSET_OF(hdfsReadStatistics, totalZeroCopyBytesRead)
+#endif
+
+void hdfs_sys_fill_hdfsReadStatistics(hdfsReadStatistics *stats) {
+    stats->totalBytesRead = 0x0102030405060708;
+    stats->totalLocalBytesRead = 0x1112131415161718;
+    stats->totalShortCircuitBytesRead = 0x2122232425262728;
+#ifdef HDFS_SYS_ZERO_COPY
+    stats->totalZeroCopyBytesRead = 0x3132333435363738;
+#endif
+}
+
+#ifdef HDFS_SYS_HEDGED_READ
+typedef struct hdfsHedgedReadMetrics hdfsHedgedReadMetrics;
+
+SIZE_OF(hdfsHedgedReadMetrics)
+ALIGN_OF(hdfsHedgedReadMetrics)
+OFFSET_OF(hdfsHedgedReadMetrics, hedgedReadOps)
+OFFSET_OF(hdfsHedgedReadMetrics, hedgedReadOpsWin)
+OFFSET_OF(hdfsHedgedReadMetrics, hedgedReadOpsInCurThread)
+
+void hdfs_sys_fill_hdfsHedgedReadMetrics(hdfsHedgedReadMetrics *metrics) {
+    metrics->hedgedReadOps = 0x0102030405060708;
+    metrics->hedgedReadOpsWin = 0x1112131415161718;
+    metrics->hedgedReadOpsInCurThread = 0x2122232425262728;
+}
+#endif
@@ -0,0 +1,146 @@
+//! Check the layout of our structs against the vendored `hdfs.h`.
+//!
+//! The C side is reported by `src/layout_checks.c`, which is compiled by
+//! `build.rs` against the header of the selected version. It's only built
+//! when `libhdfs` is compiled from source, builds linking a system
+//! `libhdfs` skip these tests.
+//!
+//! All structs are plain `#[repr(C)]` with native integer fields and no
+//! hand-packed bytes, so they are laid out by the C compiler and don't
+//! depend on endianness. The `*_round_trip` tests make sure by reading back
+//! values written by C, which matters on big-endian targets like s390x.
+
+use std::ffi::CStr;
+use std::mem::{align_of, offset_of, size_of, MaybeUninit};
+
+use super::*;
+
+macro_rules! check_layout {
+    ($name:ident, $ty:ty, $size:ident, $align:ident, { $($(#[$meta:meta])* $field:ident => $offset:ident),* $(,)? }) => {
+        extern "C" {
+            fn $size() -> usize;
+            fn $align() -> usize;
+            $($(#[$meta])* fn $offset() -> usize;)*
+        }
+
+        #[test]
+        fn $name() {
+            unsafe {
+                assert_eq!(size_of::<$ty>(), $size(), "size of {}", stringify!($ty));
+                assert_eq!(align_of::<$ty>(), $align(), "align of {}", stringify!($ty));
+                $(
+                    $(#[$meta])*
+                    assert_eq!(
+                        offset_of!($ty, $field),
+                        $offset(),
+                        "offset of {}.{}",
+                        stringify!($ty),
+                        stringify!($field),
+                    );
+                )*
+            }
+        }
+    };
+}
+
+check_layout!(
+    test_hdfs_file_info_layout,
+    hdfsFileInfo,
+    hdfs_sys_size_of_hdfsFileInfo,
+    hdfs_sys_align_of_hdfsFileInfo,
+    {
+        mKind => hdfs_sys_offset_of_hdfsFileInfo_mKind,
+        mName => hdfs_sys_offset_of_hdfsFileInfo_mName,
+        mLastMod => hdfs_sys_offset_of_hdfsFileInfo_mLastMod,
+        mSize => hdfs_sys_offset_of_hdfsFileInfo_mSize,
+        mReplication => hdfs_sys_offset_of_hdfsFileInfo_mReplication,
+        mBlockSize => hdfs_sys_offset_of_hdfsFileInfo_mBlockSize,
+        mOwner => hdfs_sys_offset_of_hdfsFileInfo_mOwner,
+        mGroup => hdfs_sys_offset_of_hdfsFileInfo_mGroup,
+        mPermissions => hdfs_sys_offset_of_hdfsFileInfo_mPermissions,
+        mLastAccess => hdfs_sys_offset_of_hdfsFileInfo_mLastAccess,
+    }
+);
+
+check_layout!(
+    test_hdfs_read_statistics_layout,
+    hdfsReadStatistics,
+    hdfs_sys_size_of_hdfsReadStatistics,
+    hdfs_sys_align_of_hdfsReadStatistics,
+    {
+        totalBytesRead => hdfs_sys_offset_of_hdfsReadStatistics_totalBytesRead,
+        totalLocalBytesRead => hdfs_sys_offset_of_hdfsReadStatistics_totalLocalBytesRead,
+        totalShortCircuitBytesRead => hdfs_sys_offset_of_hdfsReadStatistics_totalShortCircuitBytesRead,
+        #[cfg(feature = "hdfs_2_3")]
+        totalZeroCopyBytesRead => hdfs_sys_offset_of_hdfsReadStatistics_totalZeroCopyBytesRead,
+    }
+);
+
+#[cfg(feature = "hdfs_3_0")]
+check_layout!(
+    test_hdfs_hedged_read_metrics_layout,
+    hdfsHedgedReadMetrics,
+    hdfs_sys_size_of_hdfsHedgedReadMetrics,
+    hdfs_sys_align_of_hdfsHedgedReadMetrics,
+    {
+        hedgedReadOps => hdfs_sys_offset_of_hdfsHedgedReadMetrics_hedgedReadOps,
+        hedgedReadOpsWin => hdfs_sys_offset_of_hdfsHedgedReadMetrics_hedgedReadOpsWin,
+        hedgedReadOpsInCurThread => hdfs_sys_offset_of_hdfsHedgedReadMetrics_hedgedReadOpsInCurThread,
+    }
+);
+
+extern "C" {
+    fn hdfs_sys_fill_hdfsFileInfo(info: *mut hdfsFileInfo);
+    fn hdfs_sys_fill_hdfsReadStatistics(stats: *mut hdfsReadStatistics);
+    #[cfg(feature = "hdfs_3_0")]
+    fn hdfs_sys_fill_hdfsHedgedReadMetrics(metrics: *mut hdfsHedgedReadMetrics);
+}
+
+#[test]
+fn test_hdfs_file_info_round_trip() {
+    let info = unsafe {
+        let mut info = MaybeUninit::zeroed();
+        hdfs_sys_fill_hdfsFileInfo(info.as_mut_ptr());
+        info.assume_init()
+    };
+
+    assert_eq!(info.mKind, tObjectKind_kObjectKindDirectory);
+    assert_eq!(unsafe { CStr::from_ptr(info.mName) }, c"name");
+    assert_eq!(info.mLastMod, 0x01020304);
+    assert_eq!(info.mSize, 0x0102030405060708);
+    assert_eq!(info.mReplication, 0x0102);
+    assert_eq!(info.mBlockSize, 0x0807060504030201);
+    assert_eq!(unsafe { CStr::from_ptr(info.mOwner) }, c"owner");
+    assert_eq!(unsafe { CStr::from_ptr(info.mGroup) }, c"group");
+    assert_eq!(info.mPermissions, 0x0201);
+    assert_eq!(info.mLastAccess, 0x04030201);
+}
+
+#[test]
+fn test_hdfs_read_statistics_round_trip() {
+    let stats = unsafe {
+        let mut stats = MaybeUninit::zeroed();
+        hdfs_sys_fill_hdfsReadStatistics(stats.as_mut_ptr());
+        stats.assume_init()
+    };
+
+    assert_eq!(stats.totalBytesRead, 0x0102030405060708);
+    assert_eq!(stats.totalLocalBytesRead, 0x1112131415161718);
+    assert_eq!(stats.totalShortCircuitBytesRead, 0x2122232425262728);
+    #[cfg(feature = "hdfs_2_3")]
+    assert_eq!(stats.totalZeroCopyBytesRead, 0x3132333435363738);
+}
+
+#[test]
+#[cfg(feature = "hdfs_3_0")]
+fn test_hdfs_hedged_read_metrics_round_trip() {
+    let metrics = unsafe {
+        let mut metrics = MaybeUninit::zeroed();
+        hdfs_sys_fill_hdfsHedgedReadMetrics(metrics.as_mut_ptr());
+        metrics.assume_init()
+    };
+
+    assert_eq!(metrics.hedgedReadOps, 0x0102030405060708);
+    assert_eq!(metrics.hedgedReadOpsWin, 0x1112131415161718);
+    assert_eq!(metrics.hedgedReadOpsInCurThread, 0x2122232425262728);
+}
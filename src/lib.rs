@@ -2,6 +2,17 @@
 //!
 //! Work with these bindings directly is boring and error proven, please use [hdrs](https://github.com/Xuanwo/hdrs) instead if ever possible.
 //!
+//! A thin layer of safe wrappers for the most error proven patterns is provided in `safe`.
+//!
+//! ## `no_std`
+//!
+//! The raw bindings in [`raw`] only depend on `core`. Disable the default `std`
+//! feature to build without `std`, which also removes the `safe` layer:
+//!
+//! ```toml
+//! hdfs-sys = { version = "0.3", default-features = false, features = ["hdfs_2_6"] }
+//! ```
+//!
 //! ## Supported Versions
 //!
 //! To target a version of `libhdfs`, enable a Cargo features such as one of the following:
@@ -46,68 +57,36 @@
 //! export CLASSPATH=${HADOOP_HOME}/share/hadoop/common/*:${HADOOP_HOME}/share/hadoop/common/lib/*:${HADOOP_HOME}/share/hadoop/hdfs/*:${HADOOP_HOME}/share/hadoop/hdfs/lib/*:${HADOOP_HOME}/etc/hadoop/*
 //! ```
 
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 #![allow(non_snake_case)]
 #![allow(non_camel_case_types)]
 #![allow(non_upper_case_globals)]
 
+pub mod raw;
+// `raw` is empty without a version feature.
 #[cfg(feature = "hdfs_2_2")]
-mod hdfs_2_2;
-#[cfg(feature = "hdfs_2_2")]
-pub use hdfs_2_2::*;
-#[cfg(feature = "hdfs_2_3")]
-mod hdfs_2_3;
-#[cfg(feature = "hdfs_2_3")]
-pub use hdfs_2_3::*;
-#[cfg(feature = "hdfs_2_4")]
-mod hdfs_2_4;
-#[cfg(feature = "hdfs_2_4")]
-#[allow(unused_imports)]
-pub use hdfs_2_4::*;
-#[cfg(feature = "hdfs_2_5")]
-mod hdfs_2_5;
-#[cfg(feature = "hdfs_2_5")]
-#[allow(unused_imports)]
-pub use hdfs_2_5::*;
-#[cfg(feature = "hdfs_2_6")]
-mod hdfs_2_6;
-#[cfg(feature = "hdfs_2_6")]
-pub use hdfs_2_6::*;
-#[cfg(feature = "hdfs_2_7")]
-mod hdfs_2_7;
-#[cfg(feature = "hdfs_2_7")]
-pub use hdfs_2_7::*;
-#[cfg(feature = "hdfs_2_8")]
-mod hdfs_2_8;
-#[cfg(feature = "hdfs_2_8")]
-#[allow(unused_imports)]
-pub use hdfs_2_8::*;
-#[cfg(feature = "hdfs_2_9")]
-mod hdfs_2_9;
-#[cfg(feature = "hdfs_2_9")]
-pub use hdfs_2_9::*;
-#[cfg(feature = "hdfs_2_10")]
-mod hdfs_2_10;
-#[cfg(feature = "hdfs_2_10")]
-#[allow(unused_imports)]
-pub use hdfs_2_10::*;
-#[cfg(feature = "hdfs_3_0")]
-mod hdfs_3_0;
-#[cfg(feature = "hdfs_3_0")]
-pub use hdfs_3_0::*;
-#[cfg(feature = "hdfs_3_1")]
-mod hdfs_3_1;
-#[cfg(feature = "hdfs_3_1")]
-#[allow(unused_imports)]
-pub use hdfs_3_1::*;
-#[cfg(feature = "hdfs_3_2")]
-mod hdfs_3_2;
-#[cfg(feature = "hdfs_3_2")]
-#[allow(unused_imports)]
-pub use hdfs_3_2::*;
-#[cfg(feature = "hdfs_3_3")]
-mod hdfs_3_3;
-#[cfg(feature = "hdfs_3_3")]
-pub use hdfs_3_3::*;
+pub use raw::*;
+
+#[cfg(all(feature = "std", feature = "hdfs_2_2"))]
+pub mod safe;
+
+/// The version of `libhdfs` this crate links against.
+///
+/// It's the version of the compiled sources like `"3.3"`, which may be older
+/// than the enabled features on windows. A system `libhdfs` found by
+/// `HDFS_LIB_DIR`, `HADOOP_HOME` or `CONDA_PREFIX` can't be inspected while
+/// building, so it's reported as `"system"`.
+pub const HDFS_VERSION: &str = env!("HDFS_SYS_LINKED_VERSION");
 
+/// Get the version of `libhdfs` this crate links against, see [`HDFS_VERSION`].
+pub fn linked_version() -> &'static str {
+    HDFS_VERSION
+}
+
+#[cfg(test)]
+#[path = "../build/sources.rs"]
+mod build_sources;
+#[cfg(all(test, feature = "hdfs_2_2", hdfs_sys_layout_checks))]
+mod layout_checks;
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,44 @@
+/*
+ * Build the vendored `os/posix/mutexes.c` with error checking mutexes.
+ *
+ * Used instead of it by the `debug-mutexes` feature. Static mutexes are
+ * initialized as error checking where the libc supports it, the recursive
+ * `jvmMutex` of 3.x stays recursive. A failed lock or unlock, like locking
+ * a mutex twice or unlocking one owned by another thread, aborts with a
+ * message instead of deadlocking or being ignored.
+ */
+
+#include <pthread.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+static int hdfs_sys_checked_lock(pthread_mutex_t *m) {
+    int ret = pthread_mutex_lock(m);
+    if (ret) {
+        fprintf(stderr, "hdfs-sys: pthread_mutex_lock(%p) failed: %s\n",
+                (void *)m, strerror(ret));
+        abort();
+    }
+    return ret;
+}
+
+static int hdfs_sys_checked_unlock(pthread_mutex_t *m) {
+    int ret = pthread_mutex_unlock(m);
+    if (ret) {
+        fprintf(stderr, "hdfs-sys: pthread_mutex_unlock(%p) failed: %s\n",
+                (void *)m, strerror(ret));
+        abort();
+    }
+    return ret;
+}
+
+/* `pthread.h` is already included, so the vendored source keeps these. */
+#define pthread_mutex_lock hdfs_sys_checked_lock
+#define pthread_mutex_unlock hdfs_sys_checked_unlock
+#ifdef PTHREAD_ERRORCHECK_MUTEX_INITIALIZER_NP
+#undef PTHREAD_MUTEX_INITIALIZER
+#define PTHREAD_MUTEX_INITIALIZER PTHREAD_ERRORCHECK_MUTEX_INITIALIZER_NP
+#endif
+
+#include "os/posix/mutexes.c"
@@ -2,7 +2,7 @@
 //!
 //! We take this release as the baseline of hdfs-sys.
 
-use std::os::raw::*;
+use core::ffi::*;
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
@@ -10,6 +10,12 @@ pub struct hdfsBuilder {
     _unused: [u8; 0],
 }
 
+/// `time_t` is 64 bits on windows even though `long` is 32 bits there.
+#[cfg(windows)]
+pub type tTime = i64;
+#[cfg(not(windows))]
+pub type tTime = c_long;
+
 pub type tObjectKind = c_uint;
 pub const tObjectKind_kObjectKindFile: tObjectKind = 70;
 pub const tObjectKind_kObjectKindDirectory: tObjectKind = 68;
@@ -44,14 +50,14 @@ pub struct hdfsReadStatistics {
 pub struct hdfsFileInfo {
     pub mKind: tObjectKind,
     pub mName: *mut c_char,
-    pub mLastMod: c_long,
+    pub mLastMod: tTime,
     pub mSize: i64,
     pub mReplication: c_short,
     pub mBlockSize: i64,
     pub mOwner: *mut c_char,
     pub mGroup: *mut c_char,
     pub mPermissions: c_short,
-    pub mLastAccess: c_long,
+    pub mLastAccess: tTime,
 }
 
 extern "C" {
@@ -151,5 +157,5 @@ extern "C" {
         group: *const c_char,
     ) -> c_int;
     pub fn hdfsChmod(fs: hdfsFS, path: *const c_char, mode: c_short) -> c_int;
-    pub fn hdfsUtime(fs: hdfsFS, path: *const c_char, mtime: c_long, atime: c_long) -> c_int;
+    pub fn hdfsUtime(fs: hdfsFS, path: *const c_char, mtime: tTime, atime: tTime) -> c_int;
 }
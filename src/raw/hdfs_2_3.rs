@@ -119,7 +119,7 @@
 //! >
 //! ```
 
-use std::os::raw::*;
+use core::ffi::*;
 
 use crate::hdfsFile;
 
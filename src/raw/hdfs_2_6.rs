@@ -14,7 +14,7 @@
 //! >
 //! ```
 
-use std::os::raw::*;
+use core::ffi::*;
 
 use crate::hdfsFileInfo;
 
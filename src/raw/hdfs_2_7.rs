@@ -4,7 +4,7 @@
 //! - hdfsTruncateFile
 //! - hdfsUnbufferFile
 
-use std::os::raw::*;
+use core::ffi::*;
 
 use crate::{hdfsFS, hdfsFile};
 
@@ -8,7 +8,7 @@
 //! - hdfsStreamBuilderSetDefaultBlockSize
 //! - hdfsStreamBuilderBuild
 
-use std::os::raw::*;
+use core::ffi::*;
 
 use crate::{hdfsFS, hdfsFile};
 
@@ -8,7 +8,7 @@
 //! - hdfsGetLastExceptionRootCause
 //! - hdfsGetLastExceptionStackTrace
 
-use std::os::raw::*;
+use core::ffi::*;
 
 use crate::hdfsFS;
 
@@ -2,7 +2,7 @@
 //!
 //! - hdfsPreadFully
 
-use std::os::raw::*;
+use core::ffi::*;
 
 use crate::{hdfsFS, hdfsFile};
 
@@ -0,0 +1,63 @@
+//! Raw bindings to the `libhdfs` C API.
+//!
+//! This module only depends on `core`, so it can be used without `std`.
+//! Everything here is also re-exported at the crate root.
+
+#[cfg(feature = "hdfs_2_2")]
+mod hdfs_2_2;
+#[cfg(feature = "hdfs_2_2")]
+pub use hdfs_2_2::*;
+#[cfg(feature = "hdfs_2_3")]
+mod hdfs_2_3;
+#[cfg(feature = "hdfs_2_3")]
+pub use hdfs_2_3::*;
+#[cfg(feature = "hdfs_2_4")]
+mod hdfs_2_4;
+#[cfg(feature = "hdfs_2_4")]
+#[allow(unused_imports)]
+pub use hdfs_2_4::*;
+#[cfg(feature = "hdfs_2_5")]
+mod hdfs_2_5;
+#[cfg(feature = "hdfs_2_5")]
+#[allow(unused_imports)]
+pub use hdfs_2_5::*;
+#[cfg(feature = "hdfs_2_6")]
+mod hdfs_2_6;
+#[cfg(feature = "hdfs_2_6")]
+pub use hdfs_2_6::*;
+#[cfg(feature = "hdfs_2_7")]
+mod hdfs_2_7;
+#[cfg(feature = "hdfs_2_7")]
+pub use hdfs_2_7::*;
+#[cfg(feature = "hdfs_2_8")]
+mod hdfs_2_8;
+#[cfg(feature = "hdfs_2_8")]
+#[allow(unused_imports)]
+pub use hdfs_2_8::*;
+#[cfg(feature = "hdfs_2_9")]
+mod hdfs_2_9;
+#[cfg(feature = "hdfs_2_9")]
+pub use hdfs_2_9::*;
+#[cfg(feature = "hdfs_2_10")]
+mod hdfs_2_10;
+#[cfg(feature = "hdfs_2_10")]
+#[allow(unused_imports)]
+pub use hdfs_2_10::*;
+#[cfg(feature = "hdfs_3_0")]
+mod hdfs_3_0;
+#[cfg(feature = "hdfs_3_0")]
+pub use hdfs_3_0::*;
+#[cfg(feature = "hdfs_3_1")]
+mod hdfs_3_1;
+#[cfg(feature = "hdfs_3_1")]
+#[allow(unused_imports)]
+pub use hdfs_3_1::*;
+#[cfg(feature = "hdfs_3_2")]
+mod hdfs_3_2;
+#[cfg(feature = "hdfs_3_2")]
+#[allow(unused_imports)]
+pub use hdfs_3_2::*;
+#[cfg(feature = "hdfs_3_3")]
+mod hdfs_3_3;
+#[cfg(feature = "hdfs_3_3")]
+pub use hdfs_3_3::*;
@@ -0,0 +1,211 @@
+use std::future::Future;
+use std::io::{self, Read};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context, Poll};
+
+use futures_core::Stream;
+use tokio::task::{self, JoinHandle};
+use tokio_util::sync::CancellationToken;
+
+use super::{File, Filesystem, Metadata, ReadDir};
+
+/// A [`Filesystem`] for async code, running the blocking `libhdfs` calls
+/// with [`tokio::task::spawn_blocking`].
+///
+/// It must be used within a tokio runtime.
+///
+/// A blocking task can't be cancelled: dropping the returned future only
+/// detaches it, the `libhdfs` call keeps running to the end on its
+/// blocking thread. Use [`AsyncFile::read_cancellable`] to stop large
+/// reads between chunks.
+#[derive(Debug, Clone)]
+pub struct AsyncFilesystem {
+    fs: Filesystem,
+}
+
+impl AsyncFilesystem {
+    /// Wrap a connected [`Filesystem`].
+    pub fn new(fs: Filesystem) -> AsyncFilesystem {
+        AsyncFilesystem { fs }
+    }
+
+    /// Get the blocking [`Filesystem`] of this filesystem.
+    pub fn blocking(&self) -> &Filesystem {
+        &self.fs
+    }
+
+    /// Open a file for reading, see [`Filesystem::open`].
+    pub async fn open(&self, path: &str) -> io::Result<AsyncFile> {
+        let fs = self.fs.clone();
+        let path = path.to_string();
+
+        let file = task::spawn_blocking(move || fs.open(&path))
+            .await
+            .map_err(io::Error::other)??;
+        Ok(AsyncFile::new(file))
+    }
+
+    /// List the entries of the directory at `path` as a stream.
+    ///
+    /// `hdfsListDirectory` returns the whole directory at once, namenode
+    /// pages large directories by `dfs.ls.limit` inside that call. So the
+    /// listing can't be split into smaller blocking tasks: one blocking
+    /// task holds a thread until all entries are fetched, then entries are
+    /// yielded from memory without blocking. Use [`Filesystem::walk`] in
+    /// your own blocking task to bound the memory of huge trees.
+    pub fn read_dir(&self, path: &str) -> ReadDirStream {
+        let fs = self.fs.clone();
+        let path = path.to_string();
+
+        ReadDirStream {
+            state: State::Listing(task::spawn_blocking(move || fs.read_dir(&path))),
+        }
+    }
+}
+
+/// Stream of entries returned by [`AsyncFilesystem::read_dir`].
+///
+/// An error is yielded once and ends the stream.
+#[derive(Debug)]
+pub struct ReadDirStream {
+    state: State,
+}
+
+#[derive(Debug)]
+enum State {
+    Listing(JoinHandle<io::Result<ReadDir>>),
+    Entries(ReadDir),
+    Done,
+}
+
+impl Stream for ReadDirStream {
+    type Item = io::Result<Metadata>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            match &mut self.state {
+                State::Listing(handle) => {
+                    let res = ready!(Pin::new(handle).poll(cx));
+                    match res.map_err(io::Error::other).and_then(|res| res) {
+                        Ok(entries) => self.state = State::Entries(entries),
+                        Err(err) => {
+                            self.state = State::Done;
+                            return Poll::Ready(Some(Err(err)));
+                        }
+                    }
+                }
+                State::Entries(entries) => return Poll::Ready(entries.next().map(Ok)),
+                State::Done => return Poll::Ready(None),
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.state {
+            State::Listing(_) => (0, None),
+            State::Entries(entries) => entries.size_hint(),
+            State::Done => (0, Some(0)),
+        }
+    }
+}
+
+/// A [`File`] for async code, returned by [`AsyncFilesystem::open`].
+///
+/// Every call runs in a blocking task holding the file, so calls on the
+/// same file run one after another.
+#[derive(Debug)]
+pub struct AsyncFile {
+    file: Arc<Mutex<File>>,
+}
+
+impl AsyncFile {
+    /// Wrap an open [`File`].
+    pub fn new(file: File) -> AsyncFile {
+        AsyncFile {
+            file: Arc::new(Mutex::new(file)),
+        }
+    }
+
+    /// Read up to `len` bytes with a single `hdfsRead`, empty at EOF.
+    ///
+    /// Like all calls of [`AsyncFilesystem`], dropping the future doesn't
+    /// stop the read.
+    pub async fn read(&self, len: usize) -> io::Result<Vec<u8>> {
+        self.spawn(move |file| {
+            let mut buf = vec![0; len];
+            let n = file.read(&mut buf)?;
+            buf.truncate(n);
+            Ok(buf)
+        })
+        .await
+    }
+
+    /// Read up to `len` bytes in chunks of `chunk_size`, stopping early if
+    /// `token` is cancelled or the future is dropped.
+    ///
+    /// `token` is checked before every chunk, so a cancelled read stops
+    /// within one `hdfsRead` of at most `chunk_size` bytes and returns an
+    /// `Interrupted` error. The bytes read until then are dropped, but the
+    /// file offset has moved past them. Smaller chunks stop sooner at the
+    /// cost of more calls into `libhdfs`.
+    pub async fn read_cancellable(
+        &self,
+        len: usize,
+        chunk_size: usize,
+        token: &CancellationToken,
+    ) -> io::Result<Vec<u8>> {
+        if chunk_size == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "chunk size must not be zero",
+            ));
+        }
+        // Dropping the future cancels the child token only.
+        let token = token.child_token();
+        let _guard = token.clone().drop_guard();
+
+        self.spawn(move |file| read_chunks(file, len, chunk_size, &token))
+            .await
+    }
+
+    /// Run `f` with the file in a blocking task.
+    async fn spawn<T, F>(&self, f: F) -> io::Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut File) -> io::Result<T> + Send + 'static,
+    {
+        let file = self.file.clone();
+        task::spawn_blocking(move || {
+            let mut file = file
+                .lock()
+                .map_err(|_| io::Error::other("file is poisoned by a panicked call"))?;
+            f(&mut file)
+        })
+        .await
+        .map_err(io::Error::other)?
+    }
+}
+
+/// Read up to `len` bytes from `r` in chunks, checking `token` before each.
+pub(crate) fn read_chunks(
+    r: &mut impl Read,
+    len: usize,
+    chunk_size: usize,
+    token: &CancellationToken,
+) -> io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    while buf.len() < len {
+        if token.is_cancelled() {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "read cancelled"));
+        }
+        let start = buf.len();
+        buf.resize(start + chunk_size.min(len - start), 0);
+        let n = r.read(&mut buf[start..])?;
+        buf.truncate(start + n);
+        if n == 0 {
+            break;
+        }
+    }
+    Ok(buf)
+}
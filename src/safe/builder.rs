@@ -0,0 +1,443 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::io;
+use std::sync::Arc;
+
+use crate::*;
+
+use super::error;
+use super::fs::Connection;
+#[cfg(feature = "hdfs_3_0")]
+use super::HdfsError;
+use super::{to_cstring, Config, Filesystem};
+
+/// Builder of [`Filesystem`], backed by `hdfsBuilder`.
+///
+/// ```no_run
+/// use hdfs_sys::safe::ConnectBuilder;
+///
+/// let fs = ConnectBuilder::new("hdfs://127.0.0.1:9000")
+///     .connect_timeout_ms(5000)
+///     .connect()?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct ConnectBuilder {
+    namenode: String,
+    port: u16,
+    user: Option<String>,
+    kerb_ticket_cache_path: Option<String>,
+    force_new_instance: bool,
+    normalize_paths: bool,
+    confs: BTreeMap<String, String>,
+}
+
+impl ConnectBuilder {
+    /// Create a builder connecting to `namenode`, see
+    /// [`ConnectBuilder::namenode`] for the accepted forms.
+    pub fn new(namenode: &str) -> Self {
+        ConnectBuilder {
+            namenode: namenode.to_string(),
+            port: 0,
+            user: None,
+            kerb_ticket_cache_path: None,
+            force_new_instance: false,
+            normalize_paths: false,
+            confs: BTreeMap::new(),
+        }
+    }
+
+    /// Create a builder connecting to the filesystem at `url`, like
+    /// `hdfs://127.0.0.1:9000` or `viewfs://cluster`.
+    ///
+    /// Only the `hdfs` and `viewfs` schemes are accepted, with an optional
+    /// trailing `/`, other paths are rejected with `InvalidInput`. The url
+    /// is passed to `libhdfs` as is, which gets the hadoop `FileSystem` of
+    /// its scheme:
+    ///
+    /// - `hdfs://host:port` connects to that namenode, `hdfs://mycluster`
+    ///   to a HA nameservice, see [`ConnectBuilder::namenode`].
+    /// - `viewfs://cluster` resolves paths with the mount table named
+    ///   `cluster`, and `viewfs:///` with the default one. Nothing is
+    ///   checked here: the mounts must be defined by
+    ///   `fs.viewfs.mounttable.<cluster>.link.<path>` in the config files
+    ///   found in `CLASSPATH`, or set by [`ConnectBuilder::conf`], and each
+    ///   target namenode is only contacted on the first call under its
+    ///   mount.
+    pub fn from_url(url: &str) -> io::Result<Self> {
+        let invalid = |msg: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid hdfs url {url}: {msg}"),
+            )
+        };
+
+        let (scheme, rest) = url.split_once("://").ok_or_else(|| invalid("no scheme"))?;
+        let scheme = scheme.to_ascii_lowercase();
+        if scheme != "hdfs" && scheme != "viewfs" {
+            return Err(invalid("only hdfs and viewfs are supported"));
+        }
+        let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+        if !path.is_empty() {
+            return Err(invalid("a path is not allowed"));
+        }
+        let namenode = match authority {
+            // The default mount table of viewfs, `hdfs:///` would be
+            // `fs.defaultFS` which is `"default"`.
+            "" if scheme == "viewfs" => "viewfs:///".to_string(),
+            "" => return Err(invalid("no namenode")),
+            authority => format!("{scheme}://{authority}"),
+        };
+        Ok(ConnectBuilder::new(&namenode))
+    }
+
+    /// Set the namenode to connect to, passed to `hdfsBuilderSetNameNode`.
+    ///
+    /// `libhdfs` turns it into the uri of the hadoop `FileSystem` to get:
+    ///
+    /// - `"default"` uses `fs.defaultFS` in config as is, ignoring
+    ///   [`ConnectBuilder::port`].
+    /// - A uri containing `://`, like `hdfs://127.0.0.1:9000` or
+    ///   `viewfs://cluster`, is used as is, with the port appended if set.
+    /// - Anything else gets `hdfs://` prepended, so it's talked to over the
+    ///   namenode RPC port, not an http port like `9870`:
+    ///   - A hostname like `nn1.example.com` uses the port set by
+    ///     [`ConnectBuilder::port`], or the default RPC port `8020` if it's
+    ///     `0`.
+    ///   - `host:port` like `127.0.0.1:9000` uses that port, setting
+    ///     another port fails with `InvalidInput`.
+    ///   - A HA nameservice id like `mycluster` is resolved by
+    ///     `dfs.nameservices` and `dfs.ha.namenodes.mycluster` in config,
+    ///     which fail over between the namenodes. It's a logical name,
+    ///     leave the port `0`.
+    ///
+    /// An empty `namenode` fails with `InvalidInput` on
+    /// [`ConnectBuilder::connect`].
+    pub fn namenode(mut self, namenode: &str) -> Self {
+        self.namenode = namenode.to_string();
+        self
+    }
+
+    /// Get the namenode set on this builder.
+    pub fn get_namenode(&self) -> &str {
+        &self.namenode
+    }
+
+    /// Get the uri `libhdfs` will connect to, or `None` for `"default"`.
+    ///
+    /// Same as `calcEffectiveURI` in `hdfs.c`, but checked before connecting.
+    pub(crate) fn uri(&self) -> io::Result<Option<String>> {
+        if self.namenode.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "namenode must not be empty, use \"default\" for fs.defaultFS",
+            ));
+        }
+        if self.namenode == "default" {
+            return Ok(None);
+        }
+
+        let scheme = if self.namenode.contains("://") {
+            ""
+        } else {
+            "hdfs://"
+        };
+        if self.port == 0 {
+            return Ok(Some(format!("{scheme}{}", self.namenode)));
+        }
+        let has_port = self
+            .namenode
+            .rsplit_once(':')
+            .is_some_and(|(_, port)| port.bytes().all(|b| b.is_ascii_digit()));
+        if has_port {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "port {} was given, but namenode {} already contains a port",
+                    self.port, self.namenode
+                ),
+            ));
+        }
+        Ok(Some(format!("{scheme}{}:{}", self.namenode, self.port)))
+    }
+
+    /// Set the port of namenode, `0` means the port in namenode uri or config.
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    /// Set the user to connect as.
+    ///
+    /// `libhdfs` connects with `FileSystem#get(uri, conf, user)`, which
+    /// creates a remote user named `user` without credentials. The
+    /// `FileSystem` cache is keyed on that user, so every user gets its own
+    /// connection, only the JVM is shared.
+    ///
+    /// The security model depends on the cluster:
+    ///
+    /// - With simple auth, namenode trusts the user name sent by the client,
+    ///   so this works for any `user` without further setup.
+    /// - With kerberos, a remote user without credentials is rejected.
+    ///   Real impersonation needs `HADOOP_PROXY_USER` set before the JVM
+    ///   starts, which makes the whole process act as that single user on
+    ///   behalf of the kerberos login user. Namenode must allow it with
+    ///   `hadoop.proxyuser.<login user>.hosts` and `.groups`. There is no
+    ///   per connection equivalent.
+    pub fn user(mut self, user: &str) -> Self {
+        self.user = Some(user.to_string());
+        self
+    }
+
+    /// Connect as `HADOOP_USER_NAME` if it's set, no user is set yet and
+    /// the connection uses simple auth.
+    ///
+    /// With kerberos, `libhdfs` would connect as a proxy user of the
+    /// kerberos login instead, so the JVM login is kept, like hadoop does.
+    pub(crate) fn env_user(self) -> io::Result<Self> {
+        let user = env::var("HADOOP_USER_NAME").ok();
+        if self.user.is_some() || user.as_deref().is_none_or(str::is_empty) {
+            return Ok(self);
+        }
+        if !self.simple_auth()? {
+            return Ok(self);
+        }
+        Ok(self.default_user(user.as_deref()))
+    }
+
+    /// Whether `hadoop.security.authentication` is `simple`, from this
+    /// builder or else the config files, where it's the default.
+    pub(crate) fn simple_auth(&self) -> io::Result<bool> {
+        const KEY: &str = "hadoop.security.authentication";
+        let auth = match self.get_conf(KEY) {
+            Some(auth) => Some(auth.to_string()),
+            None => Config::get_str(KEY)?,
+        };
+        // Parsed case insensitively by `SecurityUtil`.
+        Ok(auth.is_none_or(|auth| auth.eq_ignore_ascii_case("simple")))
+    }
+
+    /// Connect as `user` if no user is set yet, empty means unset.
+    pub(crate) fn default_user(self, user: Option<&str>) -> Self {
+        match user {
+            Some(user) if self.user.is_none() && !user.is_empty() => self.user(user),
+            _ => self,
+        }
+    }
+
+    /// Get the user to connect as set on this builder.
+    pub fn get_user(&self) -> Option<&str> {
+        self.user.as_deref()
+    }
+
+    /// Set the path to the kerberos ticket cache.
+    pub fn kerb_ticket_cache_path(mut self, path: &str) -> Self {
+        self.kerb_ticket_cache_path = Some(path.to_string());
+        self
+    }
+
+    /// Always create a new `FileSystem` instance instead of the cached one.
+    pub fn force_new_instance(mut self, force: bool) -> Self {
+        self.force_new_instance = force;
+        self
+    }
+
+    /// Normalize all paths passed to the [`Filesystem`] with
+    /// [`normalize_path`](super::normalize_path) before calling `libhdfs`.
+    ///
+    /// Disabled by default, paths are passed as is.
+    pub fn normalize_paths(mut self, normalize: bool) -> Self {
+        self.normalize_paths = normalize;
+        self
+    }
+
+    /// Set a hadoop configuration value for this connection.
+    ///
+    /// Values are set by `hdfsBuilderConfSetStr` on top of the config loaded
+    /// from `core-site.xml` and `hdfs-site.xml`, so they override the files
+    /// for this connection only.
+    pub fn conf(mut self, key: &str, value: &str) -> Self {
+        self.confs.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Set all hadoop configuration values in `confs`, like a
+    /// `HashMap<String, String>`, see [`ConnectBuilder::conf`].
+    pub fn confs<I, K, V>(mut self, confs: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        for (k, v) in confs {
+            self = self.conf(k.as_ref(), v.as_ref());
+        }
+        self
+    }
+
+    /// Get a hadoop configuration value set on this builder.
+    pub fn get_conf(&self, key: &str) -> Option<&str> {
+        self.confs.get(key).map(|v| v.as_str())
+    }
+
+    /// Fail fast if namenode can't be reached within `ms` milliseconds.
+    ///
+    /// This sets:
+    ///
+    /// - `ipc.client.connect.timeout` to `ms`, the timeout of a single
+    ///   connect attempt.
+    /// - `ipc.client.connect.max.retries.on.timeouts` to `0`, which is `45`
+    ///   by default, so a timed out attempt is not retried.
+    ///
+    /// `libhdfs` connects lazily, so the timeout applies to the first RPC
+    /// instead of [`ConnectBuilder::connect`]. Server side limits and HA
+    /// failover may still impose their own bounds.
+    pub fn connect_timeout_ms(self, ms: u32) -> Self {
+        self.conf("ipc.client.connect.timeout", &ms.to_string())
+            .conf("ipc.client.connect.max.retries.on.timeouts", "0")
+    }
+
+    /// Fail reads and writes if a datanode doesn't respond within `ms`
+    /// milliseconds, instead of hanging.
+    ///
+    /// This sets, both in milliseconds:
+    ///
+    /// - `dfs.client.socket-timeout` to `ms`, the read timeout of sockets
+    ///   to datanodes, `60000` by default.
+    /// - `dfs.datanode.socket.write.timeout` to `ms`, the write timeout of
+    ///   the write pipeline, `480000` by default.
+    ///
+    /// A timed out read is retried on other datanodes with the block before
+    /// failing. Timeouts below a few seconds may fail healthy but busy
+    /// datanodes.
+    pub fn read_timeout_ms(self, ms: u32) -> Self {
+        self.conf("dfs.client.socket-timeout", &ms.to_string())
+            .conf("dfs.datanode.socket.write.timeout", &ms.to_string())
+    }
+
+    /// Connect to hdfs.
+    ///
+    /// With hdfs 3.0 or later, a failure caused by hadoop classes missing
+    /// from the classpath is reported as
+    /// [`HdfsError::ClasspathMissing`](super::HdfsError::ClasspathMissing),
+    /// and an authorization failure with a user set is reported as
+    /// [`HdfsError::ImpersonationDenied`](super::HdfsError::ImpersonationDenied).
+    ///
+    /// Other failures keep the kind of the `errno`, with the namenode, the
+    /// `errno` and its name in the message. With hdfs 3.0 or later, the
+    /// java stack trace is appended as well, unless the `errno` is
+    /// `EINTERNAL` like when the JVM can't be started.
+    ///
+    /// The JVM is started by the first connect with `CLASSPATH`, which is
+    /// not checked here. If it lacks the hadoop classes, `libhdfs` prints
+    /// the cause to stderr and the connect fails, see
+    /// [`init_classpath_from_hadoop_home`](super::init_classpath_from_hadoop_home).
+    ///
+    /// Connecting to hdfs doesn't always talk to namenode, an unreachable
+    /// namenode may not fail until the first call, see
+    /// [`Filesystem::check`].
+    pub fn connect(&self) -> io::Result<Filesystem> {
+        self.uri()?;
+
+        // `hdfsBuilder` only keeps the pointers, so all strings must be
+        // alive until `hdfsBuilderConnect` returns.
+        let namenode = to_cstring(&self.namenode)?;
+        let user = self.user.as_deref().map(to_cstring).transpose()?;
+        let kerb_ticket_cache_path = self
+            .kerb_ticket_cache_path
+            .as_deref()
+            .map(to_cstring)
+            .transpose()?;
+        let confs = self
+            .confs
+            .iter()
+            .map(|(k, v)| Ok((to_cstring(k)?, to_cstring(v)?)))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let bld = unsafe { hdfsNewBuilder() };
+        if bld.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+
+        unsafe {
+            hdfsBuilderSetNameNode(bld, namenode.as_ptr());
+            hdfsBuilderSetNameNodePort(bld, self.port);
+            if let Some(user) = &user {
+                hdfsBuilderSetUserName(bld, user.as_ptr());
+            }
+            if let Some(path) = &kerb_ticket_cache_path {
+                hdfsBuilderSetKerbTicketCachePath(bld, path.as_ptr());
+            }
+            if self.force_new_instance {
+                hdfsBuilderSetForceNewInstance(bld);
+            }
+            for (k, v) in &confs {
+                // Returns a negative errno on failure.
+                let ret = hdfsBuilderConfSetStr(bld, k.as_ptr(), v.as_ptr());
+                if ret != 0 {
+                    hdfsFreeBuilder(bld);
+                    return Err(io::Error::from_raw_os_error(-ret));
+                }
+            }
+        }
+
+        // `hdfsBuilderConnect` frees the builder whether it succeeds or not.
+        let fs = unsafe { hdfsBuilderConnect(bld) };
+        if fs.is_null() {
+            let err = io::Error::last_os_error();
+            #[cfg(feature = "hdfs_3_0")]
+            if let Some(err) = self.exception_error(err.raw_os_error().unwrap_or(0)) {
+                return Err(err.into());
+            }
+            return Err(self.connect_error(err));
+        }
+
+        Ok(Filesystem {
+            conn: Arc::new(Connection {
+                fs,
+                normalize_paths: self.normalize_paths,
+            }),
+        })
+    }
+
+    /// Get the [`HdfsError`] of a connect failed with `errno` from the root
+    /// cause of the java exception.
+    ///
+    /// It's always `None` for `EINTERNAL`, whose exception can't be read.
+    #[cfg(feature = "hdfs_3_0")]
+    pub(crate) fn exception_error(&self, errno: i32) -> Option<HdfsError> {
+        let cause = error::last_exception_root_cause(errno)?;
+        self.cause_error(&cause)
+    }
+
+    /// Get the [`HdfsError`] of a java exception with root cause `cause`.
+    #[cfg(feature = "hdfs_3_0")]
+    pub(crate) fn cause_error(&self, cause: &str) -> Option<HdfsError> {
+        if error::is_class_missing(cause) {
+            return Some(HdfsError::ClasspathMissing);
+        }
+        match &self.user {
+            Some(user) if error::is_access_denied(cause) => {
+                Some(HdfsError::ImpersonationDenied { user: user.clone() })
+            }
+            _ => None,
+        }
+    }
+
+    /// Add the namenode, `errno` and java stack trace to a connect error.
+    fn connect_error(&self, err: io::Error) -> io::Error {
+        let errno = err.raw_os_error().unwrap_or(0);
+        let name = error::errno_name(errno).unwrap_or("unknown");
+        #[allow(unused_mut)]
+        let mut msg = format!(
+            "connect to {}:{} failed with errno {errno} ({name}): {err}",
+            self.namenode, self.port
+        );
+        #[cfg(feature = "hdfs_3_0")]
+        if let Some(trace) = error::last_exception_stack_trace(errno) {
+            msg.push('\n');
+            msg.push_str(&trace);
+        }
+        io::Error::new(err.kind(), msg)
+    }
+}
@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use super::{Filesystem, Metadata};
+
+/// A [`Filesystem`] caching [`Filesystem::metadata`] for `ttl`.
+///
+/// Each path is statted at most once per `ttl`, later calls return the
+/// cached [`Metadata`] without an RPC to namenode. Errors are not cached.
+///
+/// The cache only knows about changes made through [`CachedFs::invalidate`]:
+/// writes, deletes and renames by this or other clients are not seen until
+/// the entry expires. It suits hot paths that change rarely, like config
+/// dirs. For mutating workloads, keep `ttl` short and invalidate paths
+/// after changing them.
+#[derive(Debug)]
+pub struct CachedFs {
+    fs: Filesystem,
+    ttl: Duration,
+    cache: Mutex<HashMap<String, (Instant, Metadata)>>,
+}
+
+impl CachedFs {
+    /// Cache the metadata of `fs` for `ttl`.
+    pub fn new(fs: Filesystem, ttl: Duration) -> CachedFs {
+        CachedFs {
+            fs,
+            ttl,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Get the uncached [`Filesystem`].
+    pub fn inner(&self) -> &Filesystem {
+        &self.fs
+    }
+
+    /// Get the metadata of `path`, from the cache if it's younger than `ttl`.
+    pub fn metadata(&self, path: &str) -> io::Result<Metadata> {
+        self.metadata_with(path, |path| self.fs.metadata(path))
+    }
+
+    /// Get the metadata of `path`, calling `stat` on a cache miss.
+    pub(crate) fn metadata_with(
+        &self,
+        path: &str,
+        stat: impl FnOnce(&str) -> io::Result<Metadata>,
+    ) -> io::Result<Metadata> {
+        if let Some((at, meta)) = self.lock().get(path) {
+            if at.elapsed() < self.ttl {
+                return Ok(meta.clone());
+            }
+        }
+
+        // Stat without holding the lock, it's a RPC.
+        let meta = stat(path)?;
+        self.lock()
+            .insert(path.to_string(), (Instant::now(), meta.clone()));
+        Ok(meta)
+    }
+
+    /// Drop the cached metadata of `path`, like after changing it.
+    pub fn invalidate(&self, path: &str) {
+        self.lock().remove(path);
+    }
+
+    /// Drop all cached metadata.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, (Instant, Metadata)>> {
+        // The map is always consistent, even if a thread panicked with it.
+        self.cache.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
@@ -0,0 +1,87 @@
+use std::ffi::CStr;
+use std::io;
+use std::ptr;
+
+use crate::*;
+
+use super::to_cstring;
+
+/// Hadoop configuration loaded from the config files on `CLASSPATH`.
+///
+/// Every call creates a new `Configuration` in the JVM from `core-site.xml`
+/// and friends, like `hdfsConfGetStr` does. Values set on a connection by
+/// [`ConnectBuilder::conf`](super::ConnectBuilder::conf) are not seen.
+///
+/// ```no_run
+/// use hdfs_sys::safe::Config;
+///
+/// let size = Config::get_int_in_range("io.file.buffer.size", 1, 1 << 30)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct Config;
+
+impl Config {
+    /// Get the value of `key`, `None` if unset.
+    pub fn get_str(key: &str) -> io::Result<Option<String>> {
+        let key = to_cstring(key)?;
+
+        let mut val = ptr::null_mut();
+        let ret = unsafe { hdfsConfGetStr(key.as_ptr(), &mut val) };
+        if ret != 0 {
+            return Err(io::Error::from_raw_os_error(ret));
+        }
+        if val.is_null() {
+            return Ok(None);
+        }
+        let s = unsafe { CStr::from_ptr(val) }
+            .to_string_lossy()
+            .into_owned();
+        unsafe { hdfsConfStrFree(val) };
+
+        Ok(Some(s))
+    }
+
+    /// Get the value of `key` as an integer, `None` if unset.
+    ///
+    /// It's parsed by `Configuration#getInt`, which also takes hex like
+    /// `0x10`. A value that is not an integer is an error.
+    pub fn get_int(key: &str) -> io::Result<Option<i32>> {
+        // `hdfsConfGetInt` returns its input for unset keys, which can't be
+        // told apart from a set value.
+        if Config::get_str(key)?.is_none() {
+            return Ok(None);
+        }
+        let c_key = to_cstring(key)?;
+
+        let mut val = 0;
+        let ret = unsafe { hdfsConfGetInt(c_key.as_ptr(), &mut val) };
+        if ret != 0 {
+            return Err(io::Error::new(
+                io::Error::from_raw_os_error(ret).kind(),
+                format!("config {key} is not an integer"),
+            ));
+        }
+        Ok(Some(val))
+    }
+
+    /// Get the value of `key` as an integer in `min..=max`, `None` if unset.
+    ///
+    /// A value out of the range is an `InvalidData` error, so a
+    /// misconfiguration is reported instead of used.
+    pub fn get_int_in_range(key: &str, min: i32, max: i32) -> io::Result<Option<i32>> {
+        if min > max {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid range {min}..={max}"),
+            ));
+        }
+        match Config::get_int(key)? {
+            Some(val) if !(min..=max).contains(&val) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("config {key} is {val}, out of range {min}..={max}"),
+            )),
+            val => Ok(val),
+        }
+    }
+}
@@ -0,0 +1,188 @@
+//! Errors of the safe wrappers and their mapping back to `errno`.
+//!
+//! Both items are also re-exported from [`safe`](super).
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Errors that say more than the `errno` set by `libhdfs`.
+///
+/// They are returned wrapped in an [`io::Error`], use [`HdfsError::from_io`]
+/// to get them back:
+///
+/// ```no_run
+/// use hdfs_sys::safe::{Filesystem, HdfsError};
+///
+/// match Filesystem::connect("default", 0) {
+///     Err(err) if matches!(HdfsError::from_io(&err), Some(HdfsError::ClasspathMissing)) => {
+///         eprintln!("{err}");
+///     }
+///     _ => {}
+/// }
+/// ```
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum HdfsError {
+    /// Hadoop classes can't be loaded by the JVM because `CLASSPATH` is
+    /// empty or incomplete.
+    ///
+    /// It's only detected with hdfs 3.0 or later, which exposes the root
+    /// cause of the last java exception. Older versions report the plain
+    /// `errno` of the failure instead. So does a JVM that can't be started
+    /// at all, with `EINTERNAL`.
+    ClasspathMissing,
+    /// The cluster doesn't allow connecting as `user`, like a proxy user
+    /// not permitted by `hadoop.proxyuser.*`.
+    ///
+    /// It's only detected with hdfs 3.0 or later from the root cause of the
+    /// java exception, and only if the failure happens while connecting.
+    /// Older versions report the plain `errno`, usually `EINTERNAL`.
+    ImpersonationDenied {
+        /// The user to connect as.
+        user: String,
+    },
+}
+
+impl HdfsError {
+    /// Get the `HdfsError` wrapped in `err`, if any.
+    pub fn from_io(err: &io::Error) -> Option<&HdfsError> {
+        err.get_ref()?.downcast_ref()
+    }
+}
+
+impl fmt::Display for HdfsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HdfsError::ClasspathMissing => write!(
+                f,
+                "hadoop classes not found, please set CLASSPATH to the output of \
+                 `hadoop classpath --glob` before connecting"
+            ),
+            HdfsError::ImpersonationDenied { user } => write!(
+                f,
+                "connecting as user {user} is denied, please check the \
+                 hadoop.proxyuser settings of the cluster"
+            ),
+        }
+    }
+}
+
+impl Error for HdfsError {}
+
+impl From<HdfsError> for io::Error {
+    fn from(err: HdfsError) -> io::Error {
+        let kind = match err {
+            HdfsError::ClasspathMissing => io::ErrorKind::NotFound,
+            HdfsError::ImpersonationDenied { .. } => io::ErrorKind::PermissionDenied,
+        };
+        io::Error::new(kind, err)
+    }
+}
+
+/// `errno` set by `libhdfs` for failures without a matching java exception.
+///
+/// It's also set when the JVM can't be started or the thread can't be
+/// attached, which frees the thread local state of `libhdfs` but leaves it
+/// set, so the last exception must not be read then.
+pub(crate) const EINTERNAL: i32 = 255;
+
+/// Get the root cause of the last java exception thrown in this thread by
+/// the call that failed with `errno`.
+///
+/// Must be called right after the failed call, the next call into
+/// `libhdfs` invalidates it. It's always `None` for `EINTERNAL`, see
+/// [`EINTERNAL`].
+#[cfg(feature = "hdfs_3_0")]
+pub(crate) fn last_exception_root_cause(errno: i32) -> Option<String> {
+    if errno == EINTERNAL {
+        return None;
+    }
+    let cause = unsafe { crate::hdfsGetLastExceptionRootCause() };
+    if cause.is_null() {
+        return None;
+    }
+    let cause = unsafe { std::ffi::CStr::from_ptr(cause) };
+    Some(cause.to_string_lossy().into_owned())
+}
+
+/// Get the stack trace of the last java exception thrown in this thread by
+/// the call that failed with `errno`.
+///
+/// Same as [`last_exception_root_cause`], it must be called right after the
+/// failed call, and it's always `None` for `EINTERNAL`.
+#[cfg(feature = "hdfs_3_0")]
+pub(crate) fn last_exception_stack_trace(errno: i32) -> Option<String> {
+    if errno == EINTERNAL {
+        return None;
+    }
+    let trace = unsafe { crate::hdfsGetLastExceptionStackTrace() };
+    if trace.is_null() {
+        return None;
+    }
+    let trace = unsafe { std::ffi::CStr::from_ptr(trace) };
+    Some(trace.to_string_lossy().into_owned())
+}
+
+/// Convert `err` back into the `errno` that `libhdfs` would set, like for
+/// returning from a C callback.
+///
+/// The `errno` of an error from `libhdfs` is returned as is. Otherwise it's
+/// mapped from the kind: `NotFound` to `ENOENT`, `PermissionDenied` to
+/// `EACCES`, `AlreadyExists` to `EEXIST`, and `EIO` for all others.
+///
+/// ```
+/// use std::io;
+/// use hdfs_sys::safe::error::to_errno;
+///
+/// assert_eq!(to_errno(&io::Error::from(io::ErrorKind::NotFound)), 2);
+/// ```
+pub fn to_errno(err: &io::Error) -> i32 {
+    if let Some(errno) = err.raw_os_error() {
+        return errno;
+    }
+    // Same on all supported platforms.
+    match err.kind() {
+        io::ErrorKind::NotFound => 2,
+        io::ErrorKind::PermissionDenied => 13,
+        io::ErrorKind::AlreadyExists => 17,
+        _ => 5,
+    }
+}
+
+/// Get the name of an `errno` set by `libhdfs`.
+///
+/// Only covers the values mapped from java exceptions that are the same on
+/// all supported platforms, plus `EINTERNAL` defined by `libhdfs` itself.
+pub(crate) fn errno_name(errno: i32) -> Option<&'static str> {
+    let name = match errno {
+        2 => "ENOENT",
+        12 => "ENOMEM",
+        13 => "EACCES",
+        17 => "EEXIST",
+        20 => "ENOTDIR",
+        22 => "EINVAL",
+        30 => "EROFS",
+        EINTERNAL => "EINTERNAL",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// Whether the root cause of an exception is a class failed to load.
+#[cfg(feature = "hdfs_3_0")]
+pub(crate) fn is_class_missing(root_cause: &str) -> bool {
+    root_cause.contains("ClassNotFoundException") || root_cause.contains("NoClassDefFoundError")
+}
+
+/// Whether the root cause of an exception is reading past the end of file.
+#[cfg(feature = "hdfs_3_3")]
+pub(crate) fn is_eof(root_cause: &str) -> bool {
+    root_cause.contains("EOFException")
+}
+
+/// Whether the root cause of an exception is an authorization failure.
+#[cfg(feature = "hdfs_3_0")]
+pub(crate) fn is_access_denied(root_cause: &str) -> bool {
+    root_cause.contains("AuthorizationException") || root_cause.contains("AccessControlException")
+}
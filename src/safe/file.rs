@@ -0,0 +1,378 @@
+use std::io::{self, IoSliceMut, Read, Seek, SeekFrom, Write};
+use std::ptr;
+#[cfg(feature = "metrics")]
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::*;
+
+#[cfg(feature = "hdfs_3_3")]
+use super::error;
+use super::fs::Connection;
+use super::metadata;
+
+/// An open hdfs file, closed on drop.
+#[derive(Debug)]
+pub struct File {
+    pub(crate) conn: Arc<Connection>,
+    pub(crate) file: hdfsFile,
+    path: String,
+    /// `hsync` after every write, set by [`Filesystem::create_synced`](super::Filesystem::create_synced).
+    sync_on_write: bool,
+    #[cfg(feature = "metrics")]
+    bytes_read: AtomicU64,
+    #[cfg(feature = "metrics")]
+    bytes_written: AtomicU64,
+}
+
+/// `hdfsFile` can be moved between threads, but must not be used by two
+/// threads at the same time.
+unsafe impl Send for File {}
+
+impl File {
+    pub(crate) fn new(conn: Arc<Connection>, file: hdfsFile, path: &str) -> File {
+        File {
+            conn,
+            file,
+            path: path.to_string(),
+            sync_on_write: false,
+            #[cfg(feature = "metrics")]
+            bytes_read: AtomicU64::new(0),
+            #[cfg(feature = "metrics")]
+            bytes_written: AtomicU64::new(0),
+        }
+    }
+
+    /// Total bytes read from this file by [`Read`] and [`PreadFile`].
+    ///
+    /// It's counted on the client, independent of the read statistics
+    /// kept by `libhdfs`.
+    #[cfg(feature = "metrics")]
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read.load(Ordering::Relaxed)
+    }
+
+    /// Total bytes written to this file by [`Write`], counted on the client.
+    #[cfg(feature = "metrics")]
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written.load(Ordering::Relaxed)
+    }
+
+    /// Count `n` bytes read, a no-op without `metrics`.
+    #[allow(unused_variables)]
+    fn count_read(&self, n: usize) {
+        #[cfg(feature = "metrics")]
+        self.bytes_read.fetch_add(n as u64, Ordering::Relaxed);
+    }
+
+    /// `hsync` after every successful write.
+    pub(crate) fn sync_on_write(mut self) -> File {
+        self.sync_on_write = true;
+        self
+    }
+
+    /// Path that used to open this file.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Close this file, returning the error that [`Drop`] would ignore.
+    ///
+    /// For files opened for writing, a successful close means all data has
+    /// been committed to hdfs.
+    ///
+    /// Errors are returned immediately without retrying, including `EINTR`.
+    /// `hdfsCloseFile` releases the stream even if closing failed, so calling
+    /// it again would be a use after free. `close` takes the file by value,
+    /// so [`Drop`] skips it afterwards.
+    pub fn close(mut self) -> io::Result<()> {
+        if self.close_raw() == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Call `hdfsCloseFile` and clear the handle, the only place closing it.
+    fn close_raw(&mut self) -> i32 {
+        let file = std::mem::replace(&mut self.file, ptr::null_mut());
+        debug_assert!(!file.is_null(), "hdfs file {} closed twice", self.path);
+
+        unsafe { hdfsCloseFile(self.conn.fs, file) }
+    }
+
+    /// Get the current offset of this file, for both reading and writing.
+    ///
+    /// It's `getPos` of the underlying java stream via `hdfsTell`, which
+    /// works for write streams in all vendored versions. For a write stream,
+    /// it's the number of bytes written so far, plus the original length if
+    /// opened for appending. Unlike [`Seek::stream_position`], this doesn't
+    /// fail for files opened for writing.
+    pub fn stream_position(&self) -> io::Result<u64> {
+        Ok(self.tell()? as u64)
+    }
+
+    /// Current offset of the stream.
+    fn tell(&self) -> io::Result<i64> {
+        let n = unsafe { hdfsTell(self.conn.fs, self.file) };
+        if n == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(n)
+    }
+
+    /// Length of this file from namenode.
+    fn len(&self) -> io::Result<i64> {
+        Ok(metadata::stat(self.conn.fs, &self.path)?.len() as i64)
+    }
+
+    /// Read all bytes until EOF into `buf` with `hdfsRead` of `chunk` bytes
+    /// each, returning the number of bytes read.
+    ///
+    /// `buf` grows by `chunk` bytes before each read, which saves the many
+    /// small reads of [`Read::read_to_end`]. A `chunk` of `0` uses the block
+    /// size of the file, capped to its length, or 1 MiB if unknown.
+    pub fn read_to_end_buffered(&mut self, buf: &mut Vec<u8>, chunk: usize) -> io::Result<usize> {
+        let chunk = match chunk {
+            0 => match metadata::stat(self.conn.fs, &self.path) {
+                Ok(meta) if meta.block_size() > 0 => {
+                    // One more byte to see EOF without another grow.
+                    meta.block_size().min(meta.len() + 1) as usize
+                }
+                _ => 1024 * 1024,
+            },
+            n => n,
+        };
+
+        let start = buf.len();
+        loop {
+            let len = buf.len();
+            buf.resize(len + chunk, 0);
+            match self.read(&mut buf[len..]) {
+                Ok(0) => {
+                    buf.truncate(len);
+                    return Ok(len - start);
+                }
+                Ok(n) => buf.truncate(len + n),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => buf.truncate(len),
+                Err(err) => {
+                    buf.truncate(len);
+                    return Err(err);
+                }
+            }
+        }
+    }
+
+    /// Get the raw `hdfsFile` handle of this file.
+    ///
+    /// The handle is only valid as long as this `File` is alive.
+    pub fn as_raw(&self) -> hdfsFile {
+        self.file
+    }
+}
+
+/// Positional reads that don't move the stream offset.
+pub trait PreadFile {
+    /// Read up to `buf.len()` bytes at `offset`, returns `0` at EOF.
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize>;
+
+    /// Read exactly `buf.len()` bytes at `offset`.
+    ///
+    /// Returns `UnexpectedEof` if the file ends before `buf` is filled, the
+    /// content of `buf` is unspecified then. By default this loops
+    /// [`PreadFile::read_at`] like [`Read::read_exact`].
+    fn read_exact_at(&self, mut offset: u64, mut buf: &mut [u8]) -> io::Result<()> {
+        while !buf.is_empty() {
+            match self.read_at(offset, buf) {
+                Ok(0) => return Err(unexpected_eof()),
+                Ok(n) => {
+                    buf = &mut buf[n..];
+                    offset += n as u64;
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Convert the offset of a positional read to the `tOffset` of `libhdfs`.
+fn pread_offset(offset: u64) -> io::Result<i64> {
+    i64::try_from(offset)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "offset is too large"))
+}
+
+fn unexpected_eof() -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "failed to fill whole buffer")
+}
+
+impl PreadFile for File {
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(i32::MAX as usize) as i32;
+        let offset = pread_offset(offset)?;
+
+        let n = unsafe {
+            hdfsPread(
+                self.conn.fs,
+                self.file,
+                offset,
+                buf.as_mut_ptr().cast(),
+                len,
+            )
+        };
+        if n == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        self.count_read(n as usize);
+
+        Ok(n as usize)
+    }
+
+    /// Since hdfs 3.3, this is a `hdfsPreadFully` per `i32::MAX` bytes.
+    #[cfg(feature = "hdfs_3_3")]
+    fn read_exact_at(&self, mut offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        for chunk in buf.chunks_mut(i32::MAX as usize) {
+            let pos = pread_offset(offset)?;
+            let ret = unsafe {
+                hdfsPreadFully(
+                    self.conn.fs,
+                    self.file,
+                    pos,
+                    chunk.as_mut_ptr().cast(),
+                    chunk.len() as i32,
+                )
+            };
+            if ret == -1 {
+                let err = io::Error::last_os_error();
+                // `EOFException` has no `errno` of its own.
+                let cause = error::last_exception_root_cause(err.raw_os_error().unwrap_or(0));
+                if cause.is_some_and(|c| error::is_eof(&c)) {
+                    return Err(unexpected_eof());
+                }
+                return Err(err);
+            }
+            self.count_read(chunk.len());
+            offset += chunk.len() as u64;
+        }
+        Ok(())
+    }
+}
+
+impl Read for File {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(i32::MAX as usize) as i32;
+
+        let n = unsafe { hdfsRead(self.conn.fs, self.file, buf.as_mut_ptr().cast(), len) };
+        if n == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        self.count_read(n as usize);
+
+        Ok(n as usize)
+    }
+
+    /// Fill `bufs` in order with successive `hdfsRead`, since `libhdfs` has
+    /// no `readv`.
+    ///
+    /// Stops at the first short read or EOF, so all bytes are contiguous in
+    /// the file. An error after some bytes have been read is dropped and
+    /// those bytes are returned, it will show up again on the next read.
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        let mut total = 0;
+        for buf in bufs.iter_mut().filter(|buf| !buf.is_empty()) {
+            let n = match self.read(buf) {
+                Ok(n) => n,
+                Err(_) if total > 0 => break,
+                Err(err) => return Err(err),
+            };
+            total += n;
+            if n < buf.len() {
+                break;
+            }
+        }
+        Ok(total)
+    }
+}
+
+impl Seek for File {
+    /// Seek to the given position.
+    ///
+    /// hdfs write streams are append only, so seeking a file opened for
+    /// writing returns [`io::ErrorKind::Unsupported`], use
+    /// [`File::stream_position`] to get the current offset of them.
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        if unsafe { hdfsFileIsOpenForWrite(self.file) } == 1 {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "hdfs can't seek a file opened for writing",
+            ));
+        }
+
+        let (base, offset) = match pos {
+            SeekFrom::Start(n) => (0, n as i64),
+            SeekFrom::Current(n) => (self.tell()?, n),
+            SeekFrom::End(n) => (self.len()?, n),
+        };
+        let target = base
+            .checked_add(offset)
+            .filter(|v| *v >= 0)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "invalid seek to a negative or overflowing position",
+                )
+            })?;
+
+        if unsafe { hdfsSeek(self.conn.fs, self.file, target) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(target as u64)
+    }
+}
+
+impl Write for File {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(i32::MAX as usize) as i32;
+
+        let n = unsafe { hdfsWrite(self.conn.fs, self.file, buf.as_ptr().cast(), len) };
+        if n == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        #[cfg(feature = "metrics")]
+        self.bytes_written.fetch_add(n as u64, Ordering::Relaxed);
+        if self.sync_on_write && unsafe { hdfsHSync(self.conn.fs, self.file) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(n as usize)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if unsafe { hdfsFlush(self.conn.fs, self.file) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+}
+
+/// Closes the file, ignoring errors.
+///
+/// A file opened for writing is `hflush`ed first, so the written data
+/// reaches the datanodes even if closing fails halfway. Drop can't return
+/// the errors of either, and it doesn't run at all if the process exits
+/// abruptly, so call [`File::close`] to know the data is committed.
+impl Drop for File {
+    fn drop(&mut self) {
+        // Already closed by `close`.
+        if self.file.is_null() {
+            return;
+        }
+        unsafe {
+            if hdfsFileIsOpenForWrite(self.file) == 1 {
+                let _ = hdfsHFlush(self.conn.fs, self.file);
+            }
+        }
+        let _ = self.close_raw();
+    }
+}
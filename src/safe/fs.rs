@@ -0,0 +1,1030 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::os::raw::c_int;
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::*;
+
+use super::metadata::{self, Metadata};
+use super::path::parent_dir;
+use super::{
+    normalize_path, to_cstring, Config, ConnectBuilder, File, OpenOptions, PreadFile, ReadDir, Walk,
+};
+
+/// `O_WRONLY` is `1` on all platforms we support.
+pub(crate) const O_WRONLY: c_int = 1;
+
+/// `O_APPEND` from `fcntl.h`, which differs between platforms.
+#[cfg(feature = "hdfs_2_9")]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) const O_APPEND: c_int = 0o2000;
+#[cfg(feature = "hdfs_2_9")]
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub(crate) const O_APPEND: c_int = 8;
+
+/// `ENAMETOOLONG` from `errno.h`, which differs between platforms.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const ENAMETOOLONG: i32 = 36;
+#[cfg(windows)]
+const ENAMETOOLONG: i32 = 38;
+#[cfg(not(any(target_os = "linux", target_os = "android", windows)))]
+const ENAMETOOLONG: i32 = 63;
+
+/// A connection returned by `libhdfs`, disconnected once the last
+/// [`Filesystem`] or [`File`] using it is dropped.
+#[derive(Debug)]
+pub(crate) struct Connection {
+    pub(crate) fs: hdfsFS,
+    /// Set by [`ConnectBuilder::normalize_paths`].
+    pub(crate) normalize_paths: bool,
+}
+
+/// `hdfsFS` is thread safe, it wraps a java `FileSystem` instance.
+unsafe impl Send for Connection {}
+unsafe impl Sync for Connection {}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        // Already disconnected by `Filesystem::disconnect`.
+        if self.fs.is_null() {
+            return;
+        }
+        unsafe {
+            let _ = hdfsDisconnect(self.fs);
+        }
+    }
+}
+
+/// Result of [`Filesystem::truncate`].
+#[cfg(feature = "hdfs_2_7")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TruncateResult {
+    /// The file has been truncated and can be appended right away.
+    Completed,
+    /// The last block is being truncated in background by block recovery.
+    InProgress,
+}
+
+/// Capacity and usage of a whole filesystem, returned by [`Filesystem::stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FsStats {
+    /// Raw capacity of all datanodes in bytes.
+    pub capacity: u64,
+    /// Bytes used by all datanodes, including replicas.
+    pub used: u64,
+    /// `capacity - used`, saturated at `0`.
+    pub remaining: u64,
+}
+
+/// Convert a size returned by `hdfsGetCapacity` or `hdfsGetUsed` to `u64`.
+///
+/// `-1` is the error sentinel with `errno` set, so this must be called right
+/// after the call. Other negative values are never valid sizes, they are
+/// rejected as `InvalidData` instead of wrapping around to a huge `u64`.
+pub(crate) fn checked_size(n: i64) -> io::Result<u64> {
+    match n {
+        -1 => Err(io::Error::last_os_error()),
+        n => u64::try_from(n).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("hdfs returned a negative size {n}"),
+            )
+        }),
+    }
+}
+
+/// A connected hdfs filesystem.
+///
+/// Files opened from this filesystem keep the connection alive, so it's
+/// fine to drop the `Filesystem` before the files.
+#[derive(Debug, Clone)]
+pub struct Filesystem {
+    pub(crate) conn: Arc<Connection>,
+}
+
+impl Filesystem {
+    /// Connect to the namenode at `namenode:port`.
+    ///
+    /// Like the hadoop CLI, the user is `HADOOP_USER_NAME` if set, else the
+    /// login user of the JVM. `HADOOP_USER_NAME` is read on every call, so
+    /// changing it later in the process still works, which the JVM alone
+    /// doesn't see. It's only used with simple auth, if
+    /// `hadoop.security.authentication` is set to anything else the
+    /// kerberos login is kept, like hadoop does.
+    ///
+    /// Use [`ConnectBuilder`] for more options, a user set by
+    /// [`ConnectBuilder::user`] always wins over `HADOOP_USER_NAME`.
+    pub fn connect(namenode: &str, port: u16) -> io::Result<Filesystem> {
+        ConnectBuilder::new(namenode)
+            .port(port)
+            .env_user()?
+            .connect()
+    }
+
+    /// Connect to the namenode at `namenode:port` as `user`.
+    ///
+    /// This is `hdfsConnectAsUser` through [`ConnectBuilder::user`], see it
+    /// for the security model. With hdfs 3.0 or later, a denied user is
+    /// reported as [`HdfsError::ImpersonationDenied`](super::HdfsError::ImpersonationDenied)
+    /// if namenode is contacted while connecting. Since connecting is lazy,
+    /// a denial usually shows up as `PermissionDenied` on the first call
+    /// instead, and older versions only report the `errno`.
+    pub fn connect_as_user(namenode: &str, port: u16, user: &str) -> io::Result<Filesystem> {
+        ConnectBuilder::new(namenode)
+            .port(port)
+            .user(user)
+            .connect()
+    }
+
+    /// Connect to the namenode at `namenode:port` with hadoop config values
+    /// in `conf`, which override the config files for this connection.
+    ///
+    /// `HADOOP_USER_NAME` is respected like [`Filesystem::connect`], see
+    /// [`ConnectBuilder::confs`] for the config.
+    pub fn connect_with_conf(
+        namenode: &str,
+        port: u16,
+        conf: &HashMap<String, String>,
+    ) -> io::Result<Filesystem> {
+        ConnectBuilder::new(namenode)
+            .port(port)
+            .confs(conf)
+            .env_user()?
+            .connect()
+    }
+
+    /// Connect to the filesystem at `url`, like `hdfs://127.0.0.1:9000` or
+    /// `viewfs://cluster` for federation, see [`ConnectBuilder::from_url`].
+    ///
+    /// `HADOOP_USER_NAME` is respected like [`Filesystem::connect`].
+    pub fn connect_url(url: &str) -> io::Result<Filesystem> {
+        ConnectBuilder::from_url(url)?.env_user()?.connect()
+    }
+
+    /// Connect to the default filesystem set by `fs.defaultFS` in the hadoop
+    /// config found in `CLASSPATH`.
+    ///
+    /// This passes `"default"` as the namenode, which `libhdfs` treats as a
+    /// sentinel for `FileSystem#get(conf)`. A null namenode means something
+    /// else to `hdfsConnect`: it always returns the local filesystem,
+    /// whatever `fs.defaultFS` is.
+    ///
+    /// `HADOOP_USER_NAME` is respected like [`Filesystem::connect`].
+    pub fn connect_default() -> io::Result<Filesystem> {
+        ConnectBuilder::new("default").env_user()?.connect()
+    }
+
+    /// Disconnect from hdfs, returning the error that [`Drop`] would ignore.
+    ///
+    /// The connection is shared with clones of this `Filesystem` and the
+    /// files opened from it, so it can only be disconnected by the last one
+    /// of them. Otherwise an error is returned and this handle is dropped
+    /// without disconnecting.
+    ///
+    /// `libhdfs` closes the java `FileSystem`, which flushes its open output
+    /// streams. Unless created with
+    /// [`ConnectBuilder::force_new_instance`], the `FileSystem` is the one
+    /// cached for the same uri and user, so other connections to it in this
+    /// process are closed as well.
+    pub fn disconnect(self) -> io::Result<()> {
+        let mut conn = Arc::try_unwrap(self.conn).map_err(|_| {
+            io::Error::other("connection is still used by other filesystems or files")
+        })?;
+        let fs = std::mem::replace(&mut conn.fs, std::ptr::null_mut());
+
+        if unsafe { hdfsDisconnect(fs) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Check whether this connection is still usable, like after a namenode
+    /// failover.
+    ///
+    /// This stats `/`, which is a real but cheap RPC to namenode.
+    /// `hdfsGetWorkingDirectory` is not used since it never leaves the
+    /// client. It's best effort: the connection may break right after a
+    /// successful check, and hadoop client retries are applied as usual, so
+    /// an unreachable namenode may take a while to be reported.
+    pub fn check(&self) -> io::Result<()> {
+        self.metadata("/").map(|_| ())
+    }
+
+    /// Set the working directory that relative paths resolve against.
+    ///
+    /// All later calls with a relative path like `data/file`, on this
+    /// `Filesystem` and its clones, resolve it against `path`. The working
+    /// directory belongs to the java `FileSystem` instance, which is shared
+    /// by all connections to the same namenode as the same user unless
+    /// [`ConnectBuilder::force_new_instance`] is set, so changing it affects
+    /// them too.
+    pub fn set_working_dir(&self, path: &str) -> io::Result<()> {
+        let p = self.c_path(path)?;
+
+        if unsafe { hdfsSetWorkingDirectory(self.conn.fs, p.as_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Get the working directory, like `hdfs://127.0.0.1:9000/user/alice`.
+    ///
+    /// It's the home directory of the user until
+    /// [`Filesystem::set_working_dir`] is called.
+    pub fn working_dir(&self) -> io::Result<String> {
+        // `ENAMETOOLONG` is set if the buffer is too small.
+        let mut buf = vec![0u8; 4096];
+        loop {
+            let ret = unsafe {
+                hdfsGetWorkingDirectory(self.conn.fs, buf.as_mut_ptr().cast(), buf.len() as _)
+            };
+            if !ret.is_null() {
+                break;
+            }
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() != Some(ENAMETOOLONG) || buf.len() >= 1 << 20 {
+                return Err(err);
+            }
+            buf.resize(buf.len() * 2, 0);
+        }
+
+        let s = CStr::from_bytes_until_nul(&buf)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        Ok(s.to_string_lossy().into_owned())
+    }
+
+    /// Get `fs.defaultFS` from the config files, like `hdfs://nn:9000`.
+    ///
+    /// It's the filesystem that `"default"` connects to, see
+    /// [`Filesystem::connect_default`]. The value is read from a new
+    /// `Configuration` loaded from `core-site.xml` on `CLASSPATH` instead of
+    /// this connection, so values set by [`ConnectBuilder::conf`] are not
+    /// seen. Returns `None` if the key is unset, which only happens without
+    /// `core-default.xml` of hadoop, where it's `file:///`.
+    pub fn default_fs(&self) -> io::Result<Option<String>> {
+        Config::get_str("fs.defaultFS")
+    }
+
+    /// Get the raw `hdfsFS` handle of this connection.
+    ///
+    /// The handle is only valid as long as this `Filesystem` is alive.
+    pub fn as_raw(&self) -> hdfsFS {
+        self.conn.fs
+    }
+
+    /// Get `path` as passed to `libhdfs`, normalized by [`normalize_path`]
+    /// if [`ConnectBuilder::normalize_paths`] is enabled.
+    pub fn path<'a>(&self, path: &'a str) -> Cow<'a, str> {
+        if self.conn.normalize_paths {
+            Cow::Owned(normalize_path(path))
+        } else {
+            Cow::Borrowed(path)
+        }
+    }
+
+    /// Convert `path` into a C string after [`Filesystem::path`].
+    fn c_path(&self, path: &str) -> io::Result<CString> {
+        to_cstring(&self.path(path))
+    }
+
+    /// Open a file for reading with the configured buffer size.
+    pub fn open(&self, path: &str) -> io::Result<File> {
+        self.open_with(path, &OpenOptions::new())
+    }
+
+    /// Open a file for reading like [`Filesystem::open`], and read the first
+    /// 64 KiB of it right away to warm up the client.
+    ///
+    /// The read is a positional `hdfsPread` into a scratch buffer, so the
+    /// returned file is still at offset `0`. It fetches the block locations
+    /// from namenode and connects to a datanode holding the first block, so
+    /// the first real read skips those round trips. This is a best effort
+    /// optimization: a failed prefetch is ignored, and the data read is
+    /// dropped, not cached by `libhdfs`. With the `metrics` feature, the
+    /// prefetched bytes are counted as read.
+    pub fn open_prefetch(&self, path: &str) -> io::Result<File> {
+        const PREFETCH_LEN: usize = 64 * 1024;
+
+        let f = self.open(path)?;
+        let mut buf = vec![0; PREFETCH_LEN];
+        let _ = f.read_at(0, &mut buf);
+        Ok(f)
+    }
+
+    /// Create a file for writing with the configured buffer size, replication
+    /// and block size, truncating it if it already exists.
+    pub fn create(&self, path: &str) -> io::Result<File> {
+        self.open_with(path, &OpenOptions::new().write(true))
+    }
+
+    /// Create a file for writing, like [`Filesystem::create`], which makes
+    /// every write durable before returning.
+    ///
+    /// hdfs has no `O_SYNC`, so each `write` is followed by `hdfsHSync`:
+    /// the data is flushed to all datanodes in the pipeline and persisted to
+    /// their disks, and visible to new readers right away. A failed sync is
+    /// returned from `write` even though the bytes are already in the stream.
+    ///
+    /// **This is slow.** Every write waits for a round trip through the
+    /// whole pipeline plus an fsync on each datanode, which can be orders of
+    /// magnitude slower than buffered writes. Prefer writing in large chunks,
+    /// or calling `hdfsHSync` only at commit points of a normal file.
+    pub fn create_synced(&self, path: &str) -> io::Result<File> {
+        Ok(self.create(path)?.sync_on_write())
+    }
+
+    /// Open a file with `opts`.
+    ///
+    /// Invalid options like a replication for reading or a block size beyond
+    /// `i32::MAX` are rejected with `InvalidInput` before calling `libhdfs`.
+    pub fn open_with(&self, path: &str, opts: &OpenOptions) -> io::Result<File> {
+        let (flags, buffer_size, replication, block_size) = opts.to_raw()?;
+        let p = self.c_path(path)?;
+
+        let f = unsafe {
+            hdfsOpenFile(
+                self.conn.fs,
+                p.as_ptr(),
+                flags,
+                buffer_size,
+                replication,
+                block_size,
+            )
+        };
+        if f.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(File::new(self.conn.clone(), f, &self.path(path)))
+    }
+
+    /// Open an existing file for appending with `hdfsStreamBuilder`.
+    ///
+    /// `buffer_size` and `block_size` must be `0`, the configured defaults.
+    /// `libhdfs` appends with `FileSystem#append(Path)`, which ignores both,
+    /// so other values return [`io::ErrorKind::Unsupported`] instead of
+    /// being dropped. In hdfs the block size is decided per file anyway,
+    /// appending can never change the size of existing blocks.
+    ///
+    /// The stream builder is added in hdfs 2.9.
+    #[cfg(feature = "hdfs_2_9")]
+    pub fn append_with(&self, path: &str, buffer_size: u32, block_size: u64) -> io::Result<File> {
+        if buffer_size != 0 || block_size != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "libhdfs can't set the buffer size or block size of an append stream",
+            ));
+        }
+        let p = self.c_path(path)?;
+
+        let f = unsafe {
+            let bld = hdfsStreamBuilderAlloc(self.conn.fs, p.as_ptr(), O_WRONLY | O_APPEND);
+            if bld.is_null() {
+                return Err(io::Error::last_os_error());
+            }
+            // The builder is freed by `hdfsStreamBuilderBuild` in all cases.
+            hdfsStreamBuilderBuild(bld)
+        };
+        if f.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(File::new(self.conn.clone(), f, &self.path(path)))
+    }
+
+    /// Create a file for writing with its own `buffer_size`, `replication`
+    /// and `block_size`, truncating it if it already exists.
+    ///
+    /// `0` is passed through to `libhdfs` as the sentinel for the
+    /// configured default of each, like [`OpenOptions`]:
+    ///
+    /// - `buffer_size` of `0` is `io.file.buffer.size`.
+    /// - `replication` of `0` is `dfs.replication`.
+    /// - `block_size` of `0` is [`Filesystem::default_block_size`] of
+    ///   `path`, which is `dfs.blocksize` unless a `viewfs` mount sets its
+    ///   own.
+    ///
+    /// `libhdfs` takes signed values, so values that would turn negative,
+    /// like a `replication` above `i16::MAX`, are rejected as
+    /// `InvalidInput` instead of wrapping around.
+    ///
+    /// `block_size` must be a multiple of `dfs.bytes-per-checksum` in the
+    /// config files, `512` by default, which is checked before calling
+    /// `libhdfs`. Namenode also rejects block sizes below
+    /// `dfs.namenode.fs-limits.min-block-size`, 1 MiB by default.
+    ///
+    /// Since hdfs 2.9, this creates the file with `hdfsStreamBuilder`, which
+    /// takes block sizes beyond `i32::MAX`. Before, it's `hdfsOpenFile` like
+    /// [`Filesystem::open_with`], which rejects them as `InvalidInput`.
+    pub fn create_with(
+        &self,
+        path: &str,
+        buffer_size: u32,
+        replication: u16,
+        block_size: u64,
+    ) -> io::Result<File> {
+        let checksum = bytes_per_checksum();
+        if !block_size.is_multiple_of(checksum) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("block size {block_size} is not a multiple of checksum size {checksum}"),
+            ));
+        }
+
+        #[cfg(not(feature = "hdfs_2_9"))]
+        return self.open_with(
+            path,
+            &OpenOptions::new()
+                .write(true)
+                .buffer_size(buffer_size)
+                .replication(replication)
+                .block_size(block_size),
+        );
+
+        #[cfg(feature = "hdfs_2_9")]
+        {
+            let buffer_size = i32::try_from(buffer_size).map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidInput, "buffer size is too large")
+            })?;
+            let replication = i16::try_from(replication).map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidInput, "replication is too large")
+            })?;
+            let block_size = i64::try_from(block_size).map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidInput, "block size is too large")
+            })?;
+            let p = self.c_path(path)?;
+
+            let f = unsafe {
+                let bld = hdfsStreamBuilderAlloc(self.conn.fs, p.as_ptr(), O_WRONLY);
+                if bld.is_null() {
+                    return Err(io::Error::last_os_error());
+                }
+                if hdfsStreamBuilderSetBufferSize(bld, buffer_size) != 0
+                    || hdfsStreamBuilderSetReplication(bld, replication) != 0
+                    || hdfsStreamBuilderSetDefaultBlockSize(bld, block_size) != 0
+                {
+                    let err = io::Error::last_os_error();
+                    hdfsStreamBuilderFree(bld);
+                    return Err(err);
+                }
+                // The builder is freed by `hdfsStreamBuilderBuild` in all cases.
+                hdfsStreamBuilderBuild(bld)
+            };
+            if f.is_null() {
+                return Err(io::Error::last_os_error());
+            }
+
+            Ok(File::new(self.conn.clone(), f, &self.path(path)))
+        }
+    }
+
+    /// Copy the local file at `local_path` into a new hdfs file at
+    /// `hdfs_path`, returning the number of bytes copied.
+    ///
+    /// The data is streamed through a single buffer of `buffer_size`, and the
+    /// hdfs file is closed before returning, so everything is committed to
+    /// hdfs once this succeeds. An existing file at `hdfs_path` is truncated.
+    pub fn put(&self, local_path: &Path, hdfs_path: &str, buffer_size: usize) -> io::Result<u64> {
+        let mut buf = copy_buffer(buffer_size)?;
+        let mut local = fs::File::open(local_path)?;
+        let mut f = self.create(hdfs_path)?;
+
+        let n = copy_with_buffer(&mut local, &mut f, &mut buf, |_| {})?;
+        f.close()?;
+        Ok(n)
+    }
+
+    /// Copy the hdfs file at `hdfs_path` into a new local file at
+    /// `local_path`, returning the number of bytes copied.
+    ///
+    /// The data is streamed through a single buffer of `buffer_size`, and the
+    /// local file is synced to disk before returning. An existing file at
+    /// `local_path` is truncated.
+    pub fn get(&self, hdfs_path: &str, local_path: &Path, buffer_size: usize) -> io::Result<u64> {
+        let mut buf = copy_buffer(buffer_size)?;
+        let mut f = self.open(hdfs_path)?;
+        let mut local = fs::File::create(local_path)?;
+
+        let n = copy_with_buffer(&mut f, &mut local, &mut buf, |_| {})?;
+        local.sync_all()?;
+        f.close()?;
+        Ok(n)
+    }
+
+    /// Copy the hdfs file at `src` into a new hdfs file at `dst`, returning
+    /// the number of bytes copied.
+    ///
+    /// The data is streamed through this client in a single buffer of
+    /// `buffer_size`, `progress` is called with the bytes copied so far after
+    /// every write. `dst` is closed before returning, so the copy is
+    /// committed once this succeeds. An existing file at `dst` is truncated.
+    ///
+    /// `hdfsCopy` runs `FileUtil#copy` in the JVM without any progress, but
+    /// also copies whole directories and between two connections. Prefer
+    /// this for large single files where progress matters.
+    pub fn copy_file(
+        &self,
+        src: &str,
+        dst: &str,
+        buffer_size: usize,
+        progress: impl FnMut(u64),
+    ) -> io::Result<u64> {
+        let mut buf = copy_buffer(buffer_size)?;
+        let mut r = self.open(src)?;
+        let mut w = self.create(dst)?;
+
+        let n = copy_with_buffer(&mut r, &mut w, &mut buf, progress)?;
+        w.close()?;
+        r.close()?;
+        Ok(n)
+    }
+
+    /// Truncate the file at `path` to `new_len` bytes.
+    ///
+    /// If `new_len` is on a block boundary, the truncation completes right
+    /// away. Otherwise namenode starts block recovery to truncate the last
+    /// block and returns [`TruncateResult::InProgress`]. The file is under
+    /// a recovery lease until then, so opening it for append fails, while
+    /// readers may still see the old length. Callers that need the
+    /// truncation to be done can poll [`Filesystem::metadata`] until
+    /// [`Metadata::len`] matches `new_len`.
+    #[cfg(feature = "hdfs_2_7")]
+    pub fn truncate(&self, path: &str, new_len: u64) -> io::Result<TruncateResult> {
+        let new_len = i64::try_from(new_len)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "length is too large"))?;
+        let p = self.c_path(path)?;
+
+        match unsafe { hdfsTruncateFile(self.conn.fs, p.as_ptr(), new_len) } {
+            1 => Ok(TruncateResult::Completed),
+            0 => Ok(TruncateResult::InProgress),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+
+    /// Get the metadata of `path`.
+    pub fn metadata(&self, path: &str) -> io::Result<Metadata> {
+        metadata::stat(self.conn.fs, &self.path(path))
+    }
+
+    /// List the entries of the directory at `path`.
+    ///
+    /// The paths of the entries are fully qualified, like
+    /// `hdfs://namenode:9000/tmp/file`. Use [`ReadDir::files`] or
+    /// [`ReadDir::dirs`] to only get one kind of them.
+    pub fn read_dir(&self, path: &str) -> io::Result<ReadDir> {
+        metadata::list(self.conn.fs, &self.path(path)).map(ReadDir::new)
+    }
+
+    /// Count the files directly in the directory at `path` per block size.
+    ///
+    /// The block sizes come with the entries of one `hdfsListDirectory`, so
+    /// this is a single listing instead of a stat per file, cheap even for
+    /// large directories. Subdirectories are neither counted nor descended
+    /// into. Block sizes are the ones set when each file was created, not
+    /// the current `dfs.blocksize`.
+    pub fn block_size_histogram(&self, path: &str) -> io::Result<BTreeMap<u64, usize>> {
+        let mut histogram = BTreeMap::new();
+        for entry in self.read_dir(path)?.files() {
+            *histogram.entry(entry.block_size()).or_insert(0) += 1;
+        }
+        Ok(histogram)
+    }
+
+    /// Walk the directory tree at `path` depth-first, excluding `path` itself.
+    ///
+    /// A directory is yielded before its entries. Errors while listing a
+    /// directory are yielded in place of its entries, and the walk goes on
+    /// with the next one.
+    ///
+    /// Symlinks are disabled in hdfs by default, and listed as files if
+    /// enabled, so they are never followed and the walk can't loop.
+    pub fn walk(&self, path: &str) -> Walk {
+        Walk::new(self.clone(), path)
+    }
+
+    /// Delete the directory at `path` and everything below it in batches.
+    ///
+    /// The children of `path` are deleted recursively one by one, `progress`
+    /// is called with the running count of deleted children after every
+    /// batch of `batch_size` children. The now empty `path` is removed last.
+    ///
+    /// A single recursive `hdfsDelete` on a huge tree can time out the
+    /// namenode RPC. This trades the atomicity of it for resilience: if an
+    /// error is returned, some children may have been deleted already.
+    pub fn delete_batched(
+        &self,
+        path: &str,
+        batch_size: usize,
+        mut progress: impl FnMut(usize),
+    ) -> io::Result<()> {
+        if batch_size == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "batch size must not be zero",
+            ));
+        }
+
+        let entries: Vec<_> = self.read_dir(path)?.collect();
+        let mut deleted = 0;
+        for batch in entries.chunks(batch_size) {
+            for entry in batch {
+                self.delete(entry.path(), true)?;
+            }
+            deleted += batch.len();
+            progress(deleted);
+        }
+
+        self.delete(path, false)
+    }
+
+    /// Delete `path`, directories are only deleted if `recursive` or empty.
+    fn delete(&self, path: &str, recursive: bool) -> io::Result<()> {
+        let p = self.c_path(path)?;
+
+        if unsafe { hdfsDelete(self.conn.fs, p.as_ptr(), recursive as c_int) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Rename `from` to `to` within this filesystem.
+    ///
+    /// Without `overwrite`, this is a single `hdfsRename`, which fails if
+    /// `to` exists as a file, reported as `AlreadyExists`. Like
+    /// `FileSystem#rename`, an existing directory `to` is not a failure:
+    /// `from` is moved into it.
+    ///
+    /// With `overwrite`, an existing `to` is deleted first, non recursively,
+    /// so a non empty directory is never replaced. The delete and the rename
+    /// are two RPCs: other clients may see `to` missing in between, and if
+    /// the rename fails, `to` is gone already.
+    pub fn rename(&self, from: &str, to: &str, overwrite: bool) -> io::Result<()> {
+        if overwrite {
+            match self.delete(to, false) {
+                Ok(()) => {}
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        let (f, t) = (self.c_path(from)?, self.c_path(to)?);
+        if unsafe { hdfsRename(self.conn.fs, f.as_ptr(), t.as_ptr()) } != 0 {
+            let err = io::Error::last_os_error();
+            // `FileSystem#rename` returns false instead of throwing, so
+            // `errno` doesn't tell why it failed.
+            if !overwrite && self.exists(from)? && self.exists(to)? {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("rename {from} to {to} failed: destination exists"),
+                ));
+            }
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    /// Move the file at `from` to `to`, checking both ends first.
+    ///
+    /// The rename itself is atomic in hdfs: a single namenode operation
+    /// after which readers see either the old or the new path, never both
+    /// or neither. The checks before it are not part of that operation, so
+    /// a concurrent client may still change either path in between.
+    ///
+    /// - A missing `from`, or a missing parent dir of `to`, is `NotFound`.
+    /// - A directory at `from` is `InvalidInput`, this only moves files.
+    /// - An existing `to` is `AlreadyExists`, even if it's a directory,
+    ///   which [`Filesystem::rename`] would move `from` into.
+    pub fn move_file(&self, from: &str, to: &str) -> io::Result<()> {
+        if self.metadata(from)?.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("move {from} failed: it's a directory"),
+            ));
+        }
+
+        let parent = parent_dir(&self.path(to));
+        if !self.is_dir(&parent)? {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("move {from} to {to} failed: {parent} is not a directory"),
+            ));
+        }
+        if self.exists(to)? {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("move {from} to {to} failed: destination exists"),
+            ));
+        }
+
+        self.rename(from, to, false)
+    }
+
+    /// Check whether `path` is encrypted, that is, inside an encryption zone.
+    ///
+    /// hdfs only sets the encryption flag on the file status returned by
+    /// `hdfsGetPathInfo`, so this takes a namenode RPC like [`Filesystem::metadata`].
+    #[cfg(feature = "hdfs_2_6")]
+    pub fn is_encrypted(&self, path: &str) -> io::Result<bool> {
+        let p = self.c_path(path)?;
+
+        let info = unsafe { hdfsGetPathInfo(self.conn.fs, p.as_ptr()) };
+        if info.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        // An error must not be taken as not encrypted, read `errno` before
+        // freeing the info.
+        let encrypted = match unsafe { hdfsFileIsEncrypted(info) } {
+            1 => Ok(true),
+            0 => Ok(false),
+            _ => Err(io::Error::last_os_error()),
+        };
+        unsafe { hdfsFreeFileInfo(info, 1) };
+
+        encrypted
+    }
+
+    /// Check whether `path` exists.
+    ///
+    /// `hdfsExists` sets `ENOENT` if `path` doesn't exist, which is mapped to
+    /// `Ok(false)`, all other errors like a broken connection are returned.
+    /// Note that `libhdfs` also maps some java exceptions to `ENOENT`, so
+    /// those can't be told apart from a missing path.
+    pub fn exists(&self, path: &str) -> io::Result<bool> {
+        let p = self.c_path(path)?;
+
+        if unsafe { hdfsExists(self.conn.fs, p.as_ptr()) } == 0 {
+            return Ok(true);
+        }
+        let err = io::Error::last_os_error();
+        match err.kind() {
+            io::ErrorKind::NotFound => Ok(false),
+            _ => Err(err),
+        }
+    }
+
+    /// Check whether `path` exists and is a directory.
+    pub fn is_dir(&self, path: &str) -> io::Result<bool> {
+        match self.metadata(path) {
+            Ok(meta) => Ok(meta.is_dir()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Create `path` and all missing parents, like `mkdir -p`.
+    ///
+    /// An existing directory at `path` is a success, while an existing file
+    /// fails with `AlreadyExists`. `hdfsCreateDirectory` reports either as
+    /// a failure or not depending on the hdfs version, so the path is
+    /// checked with [`Filesystem::metadata`] after a failure.
+    pub fn create_dir_all(&self, path: &str) -> io::Result<()> {
+        let p = self.c_path(path)?;
+
+        if unsafe { hdfsCreateDirectory(self.conn.fs, p.as_ptr()) } == 0 {
+            return Ok(());
+        }
+        let err = io::Error::last_os_error();
+        match self.metadata(path) {
+            Ok(meta) if meta.is_dir() => Ok(()),
+            Ok(_) => Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("create dir {path} failed: exists and is not a directory"),
+            )),
+            Err(_) => Err(err),
+        }
+    }
+
+    /// Set the replication of the file at `path`.
+    ///
+    /// The namenode schedules the new replicas or removes the excess ones
+    /// asynchronously, use [`Filesystem::set_replication_and_wait`] to wait for it.
+    pub fn set_replication(&self, path: &str, replication: u16) -> io::Result<()> {
+        let replication = i16::try_from(replication)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "replication is too large"))?;
+        let p = self.c_path(path)?;
+
+        if unsafe { hdfsSetReplication(self.conn.fs, p.as_ptr(), replication) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Set the modification and access time of the file at `path`.
+    ///
+    /// `None` leaves the time unchanged, it's the `-1` of `hdfsUtime`. Times
+    /// are truncated to whole seconds since unix epoch, and times before
+    /// the epoch are rejected as `InvalidInput`.
+    pub fn set_times(
+        &self,
+        path: &str,
+        mtime: Option<SystemTime>,
+        atime: Option<SystemTime>,
+    ) -> io::Result<()> {
+        let to_secs = |time: Option<SystemTime>| -> io::Result<tTime> {
+            let Some(time) = time else { return Ok(-1) };
+            time.duration_since(UNIX_EPOCH)
+                .ok()
+                .and_then(|d| tTime::try_from(d.as_secs()).ok())
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "time is out of range"))
+        };
+        let (mtime, atime) = (to_secs(mtime)?, to_secs(atime)?);
+        let p = self.c_path(path)?;
+
+        if unsafe { hdfsUtime(self.conn.fs, p.as_ptr(), mtime, atime) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Set the replication of the file at `path` and wait until every block
+    /// of it is stored on exactly `replication` datanodes.
+    ///
+    /// `mReplication` returned by `hdfsGetPathInfo` changes right away, while
+    /// the replicas on disk lag behind it. So this polls the block locations
+    /// instead, and returns a `TimedOut` error if they don't match after
+    /// `timeout`. A replication higher than the number of live datanodes
+    /// can never be reached.
+    pub fn set_replication_and_wait(
+        &self,
+        path: &str,
+        replication: u16,
+        timeout: Duration,
+    ) -> io::Result<()> {
+        self.set_replication(path, replication)?;
+
+        let deadline = Instant::now() + timeout;
+        let interval = Duration::from_millis(100);
+        loop {
+            let len = self.metadata(path)?.len();
+            let hosts = self.block_hosts(path, 0, len)?;
+            if hosts.iter().all(|v| v.len() == replication as usize) {
+                return Ok(());
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("replication of {path} didn't reach {replication} in {timeout:?}"),
+                ));
+            }
+            thread::sleep(interval.min(deadline - now));
+        }
+    }
+
+    /// Get the capacity and usage of this filesystem.
+    ///
+    /// These are raw bytes of the whole filesystem across all datanodes, not
+    /// of a path, so replicas count several times. `remaining` is computed
+    /// from the two values rather than asked from namenode, it also counts
+    /// space used by non-hdfs data as remaining.
+    pub fn stats(&self) -> io::Result<FsStats> {
+        let capacity = checked_size(unsafe { hdfsGetCapacity(self.conn.fs) })?;
+        let used = checked_size(unsafe { hdfsGetUsed(self.conn.fs) })?;
+
+        Ok(FsStats {
+            capacity,
+            used,
+            remaining: capacity.saturating_sub(used),
+        })
+    }
+
+    /// Get the default block size of files created at `path`.
+    ///
+    /// This respects the config of the filesystem `path` belongs to, like a
+    /// mount point of `viewfs`, so it may differ from the global
+    /// `dfs.blocksize`. `path` doesn't need to exist.
+    ///
+    /// `hdfsGetDefaultBlockSizeAtPath` is available since hdfs 2.2, the
+    /// oldest version we support, so there is no fallback to
+    /// `hdfsGetDefaultBlockSize`.
+    pub fn default_block_size(&self, path: &str) -> io::Result<u64> {
+        let p = self.c_path(path)?;
+
+        let n = unsafe { hdfsGetDefaultBlockSizeAtPath(self.conn.fs, p.as_ptr()) };
+        if n == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(n as u64)
+    }
+
+    /// Get the hosts storing each block of `path` in `start..start + length`.
+    ///
+    /// Every item of the returned vec is the list of datanode hostnames
+    /// holding one block. An empty range returns an empty vec.
+    pub fn block_hosts(&self, path: &str, start: u64, length: u64) -> io::Result<Vec<Vec<String>>> {
+        if length == 0 {
+            return Ok(Vec::new());
+        }
+        let start = i64::try_from(start)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "start is too large"))?;
+        let length = i64::try_from(length)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "length is too large"))?;
+        let p = self.c_path(path)?;
+
+        let hosts = unsafe { hdfsGetHosts(self.conn.fs, p.as_ptr(), start, length) };
+        if hosts.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+
+        // Both levels are null terminated arrays.
+        let mut blocks = Vec::new();
+        unsafe {
+            let mut block = hosts;
+            while !(*block).is_null() {
+                let mut names = Vec::new();
+                let mut host = *block;
+                while !(*host).is_null() {
+                    names.push(CStr::from_ptr(*host).to_string_lossy().into_owned());
+                    host = host.add(1);
+                }
+                blocks.push(names);
+                block = block.add(1);
+            }
+            hdfsFreeHosts(hosts);
+        }
+
+        Ok(blocks)
+    }
+
+    /// Check whether any block of `path` in `offset..offset + length` has a
+    /// replica on `hostname`, see [`Filesystem::block_hosts`].
+    ///
+    /// Hostnames are compared case insensitively without the domain, so
+    /// `DN1.example.com` matches `dn1`. IP addresses are compared as is.
+    /// Datanodes are named as they registered to namenode, which may differ
+    /// from what `hostname` returns on that host with multiple interfaces.
+    pub fn is_local(
+        &self,
+        path: &str,
+        offset: u64,
+        length: u64,
+        hostname: &str,
+    ) -> io::Result<bool> {
+        let hostname = short_host(hostname);
+        let blocks = self.block_hosts(path, offset, length)?;
+        Ok(blocks
+            .iter()
+            .flatten()
+            .any(|host| short_host(host) == hostname))
+    }
+}
+
+/// Lowercase `host` and strip its domain, IP addresses are kept as is.
+pub(crate) fn short_host(host: &str) -> String {
+    let host = host.to_ascii_lowercase();
+    if host.parse::<std::net::IpAddr>().is_ok() {
+        return host;
+    }
+    match host.split_once('.') {
+        Some((name, _)) => name.to_string(),
+        None => host,
+    }
+}
+
+/// Allocate the buffer used by [`copy_with_buffer`].
+fn copy_buffer(buffer_size: usize) -> io::Result<Vec<u8>> {
+    if buffer_size == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "buffer size must not be zero",
+        ));
+    }
+    Ok(vec![0; buffer_size])
+}
+
+/// Copy all of `r` into `w` through `buf`, calling `progress` with the
+/// running total after every write.
+fn copy_with_buffer(
+    r: &mut impl Read,
+    w: &mut impl Write,
+    buf: &mut [u8],
+    mut progress: impl FnMut(u64),
+) -> io::Result<u64> {
+    let mut total = 0;
+    loop {
+        let n = match r.read(buf) {
+            Ok(0) => return Ok(total),
+            Ok(n) => n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        w.write_all(&buf[..n])?;
+        total += n as u64;
+        progress(total);
+    }
+}
+
+/// Get `dfs.bytes-per-checksum` from the config files, `512` if unset.
+fn bytes_per_checksum() -> u64 {
+    match Config::get_int_in_range("dfs.bytes-per-checksum", 1, i32::MAX) {
+        Ok(Some(val)) => val as u64,
+        _ => 512,
+    }
+}
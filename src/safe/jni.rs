@@ -0,0 +1,84 @@
+/// Opaque `JNIEnv` of the JVM started by `libhdfs`.
+///
+/// It's the `JNIEnv` of `jni.h`, which is a pointer to the function table.
+/// Cast it to the type of your JNI bindings, like `jni::sys::JNIEnv`.
+#[repr(C)]
+pub struct JNIEnv {
+    _private: [u8; 0],
+}
+
+extern "C" {
+    /// Internal helper of `libhdfs` in `jni_helper.c`, only reachable when
+    /// linked statically since its symbol is hidden in `libhdfs.so`.
+    fn getJNIEnv() -> *mut JNIEnv;
+    /// Defined in `src/thread_detach.c`, returns `0` or an error code of
+    /// the platform.
+    #[cfg(feature = "hdfs_3_0")]
+    fn hdfs_sys_detach_current_thread() -> i32;
+}
+
+/// Get the `JNIEnv` of the current thread from `libhdfs`, or null on failure.
+///
+/// The JVM is created if it's not running yet, the same way the first call
+/// into `libhdfs` does, reading `CLASSPATH` and `LIBHDFS_OPTS`. The thread
+/// is attached to it if needed, and the causes of failures are printed to
+/// stderr by `libhdfs`.
+///
+/// This is meant for making JNI calls that `libhdfs` doesn't cover in the
+/// same JVM, like wrapping the returned pointer with
+/// `jni::JNIEnv::from_raw`. Enable the `jni-interop` feature to use it,
+/// which builds the `vendored` sources.
+///
+/// # Safety
+///
+/// Getting the pointer is safe, but all uses of it are `unsafe` JNI calls.
+/// It's owned by `libhdfs` and must be used carefully:
+///
+/// - It's only valid on the current thread. Never send it, or anything
+///   borrowing it, to another thread. Call this again on each thread.
+/// - The thread is attached by `libhdfs`, which detaches it when the thread
+///   exits. Never call `DetachCurrentThread` on it, `libhdfs` would keep
+///   using the dangling env of this thread afterwards. Never call
+///   `DestroyJavaVM` either.
+/// - Attaching the thread with another library like
+///   `JavaVM::attach_current_thread` is fine, since the JVM hands out the
+///   same env, but a guard from it detaches the thread on drop, which
+///   breaks `libhdfs` the same way. Use `attach_current_thread_permanently`
+///   or just this function instead.
+/// - `libhdfs` checks and clears pending java exceptions after each of its
+///   own calls. Clear any exception you leave pending before calling into
+///   `libhdfs` again, the same as before returning from a JNI call.
+/// - Local references created with it are only freed when the thread
+///   detaches, which may be never for a long lived thread. Wrap them in a
+///   local frame or delete them explicitly.
+pub fn current_jni_env() -> *mut JNIEnv {
+    unsafe { getJNIEnv() }
+}
+
+/// Detach the current thread from the JVM, releasing the JNI resources that
+/// `libhdfs` keeps for it.
+///
+/// `libhdfs` attaches a thread on its first call and only detaches it when
+/// the thread exits, so a long lived thread keeps its `java.lang.Thread`
+/// and local references until then. This detaches it right away and frees
+/// the thread local state of `libhdfs`. It's a no-op for a thread that
+/// `libhdfs` never attached, so calling it twice is fine.
+///
+/// Calling into `libhdfs` after this is safe, the thread is attached again
+/// by the next call, like by [`current_jni_env`]. But pointers from
+/// [`current_jni_env`] are dangling afterwards, and so are the local
+/// references created on this thread.
+///
+/// There is no way to shut down the JVM: `libhdfs` doesn't expose
+/// `DestroyJavaVM`, and a JVM can't be started again in the same process
+/// anyway. The JVM threads stay until the process exits.
+///
+/// Only available with hdfs 3.0 or later, it relies on the thread local
+/// state of the vendored sources.
+#[cfg(feature = "hdfs_3_0")]
+pub fn detach_current_thread() -> std::io::Result<()> {
+    match unsafe { hdfs_sys_detach_current_thread() } {
+        0 => Ok(()),
+        ret => Err(std::io::Error::from_raw_os_error(ret)),
+    }
+}
@@ -0,0 +1,194 @@
+use std::ffi::CStr;
+use std::io;
+use std::os::raw::c_char;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::*;
+
+use super::{to_cstring, Permissions};
+
+/// Metadata of a file or directory, mirrors [`std::fs::Metadata`].
+///
+/// All fields are copied out of `hdfsFileInfo`, so `Metadata` doesn't hold
+/// any memory owned by `libhdfs`. Strings are converted lossily, invalid
+/// UTF-8 is replaced with `U+FFFD`.
+///
+/// Erasure coded files of hdfs 3 are reported as regular files. No vendored
+/// `libhdfs` exposes the erasure coding policy: `hdfsFileInfo` has no field
+/// for it, and the extended file info of 3.3 only carries the encryption
+/// flag of [`Filesystem::is_encrypted`](super::Filesystem::is_encrypted).
+/// Use `hdfs ec -getPolicy` or the java API to tell them apart.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Metadata {
+    path: String,
+    is_dir: bool,
+    size: i64,
+    replication: i16,
+    block_size: i64,
+    owner: String,
+    group: String,
+    permissions: i16,
+    last_mod: tTime,
+    last_access: tTime,
+}
+
+impl Metadata {
+    /// Copy metadata out of a `hdfsFileInfo`.
+    ///
+    /// # Safety
+    ///
+    /// All pointers in `info` must be either null or valid C strings.
+    pub(crate) unsafe fn from_raw(info: &hdfsFileInfo) -> Metadata {
+        Metadata {
+            path: lossy_string(info.mName),
+            is_dir: info.mKind == tObjectKind_kObjectKindDirectory,
+            size: info.mSize,
+            replication: info.mReplication,
+            block_size: info.mBlockSize,
+            owner: lossy_string(info.mOwner),
+            group: lossy_string(info.mGroup),
+            permissions: info.mPermissions,
+            last_mod: info.mLastMod,
+            last_access: info.mLastAccess,
+        }
+    }
+
+    /// Full path returned by namenode, like `hdfs://127.0.0.1:9000/tmp/file`.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Size of the file in bytes, `0` for directories.
+    pub fn len(&self) -> u64 {
+        self.size as u64
+    }
+
+    /// Whether the file is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether this metadata is for a directory.
+    pub fn is_dir(&self) -> bool {
+        self.is_dir
+    }
+
+    /// Whether this metadata is for a regular file.
+    pub fn is_file(&self) -> bool {
+        !self.is_dir
+    }
+
+    /// Replication factor of the file, `0` for directories.
+    pub fn replication(&self) -> u16 {
+        self.replication as u16
+    }
+
+    /// Block size of the file in bytes, `0` for directories.
+    pub fn block_size(&self) -> u64 {
+        self.block_size as u64
+    }
+
+    /// Owner of the file.
+    ///
+    /// Invalid UTF-8 in the name is replaced with `U+FFFD`, so it may not
+    /// round trip to namenode for non UTF-8 users.
+    pub fn owner(&self) -> &str {
+        &self.owner
+    }
+
+    /// Group of the file.
+    ///
+    /// Invalid UTF-8 is replaced like [`Metadata::owner`].
+    pub fn group(&self) -> &str {
+        &self.group
+    }
+
+    /// Permissions of the file, like `0o644`.
+    pub fn permissions(&self) -> Permissions {
+        Permissions::from_mode(self.permissions as u16)
+    }
+
+    /// Last modification time of the file.
+    ///
+    /// Namenode keeps it in milliseconds, but every vendored `libhdfs` from
+    /// 2.2 to 3.3 divides it by `1000` into the seconds of `mLastMod`, so
+    /// the precision is whole seconds.
+    pub fn modified(&self) -> io::Result<SystemTime> {
+        to_system_time(self.last_mod)
+    }
+
+    /// Last access time of the file.
+    ///
+    /// Namenode only records access time with a precision of
+    /// `dfs.namenode.accesstime.precision`, one hour by default. Setting it
+    /// to `0` disables access time, then this returns `Unsupported`.
+    pub fn accessed(&self) -> io::Result<SystemTime> {
+        if self.last_access == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "access time is not recorded by namenode",
+            ));
+        }
+        to_system_time(self.last_access)
+    }
+}
+
+/// Get the metadata of `path` with `hdfsGetPathInfo`.
+pub(crate) fn stat(fs: hdfsFS, path: &str) -> io::Result<Metadata> {
+    let p = to_cstring(path)?;
+
+    let info = unsafe { hdfsGetPathInfo(fs, p.as_ptr()) };
+    if info.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+    let meta = unsafe { Metadata::from_raw(&*info) };
+    unsafe { hdfsFreeFileInfo(info, 1) };
+
+    Ok(meta)
+}
+
+/// List the entries of the directory at `path`.
+pub(crate) fn list(fs: hdfsFS, path: &str) -> io::Result<Vec<Metadata>> {
+    let p = to_cstring(path)?;
+
+    let mut n = 0;
+    let infos = unsafe { hdfsListDirectory(fs, p.as_ptr(), &mut n) };
+    if infos.is_null() {
+        let err = io::Error::last_os_error();
+        // An empty directory returns null with `errno` set to `0`.
+        return match err.raw_os_error() {
+            Some(0) => Ok(Vec::new()),
+            _ => Err(err),
+        };
+    }
+    let entries = unsafe { std::slice::from_raw_parts(infos, n as usize) }
+        .iter()
+        .map(|info| unsafe { Metadata::from_raw(info) })
+        .collect();
+    unsafe { hdfsFreeFileInfo(infos, n) };
+
+    Ok(entries)
+}
+
+/// Convert seconds since unix epoch into `SystemTime`.
+///
+/// `tTime` of `hdfsFileInfo` is always seconds, never milliseconds.
+fn to_system_time(secs: tTime) -> io::Result<SystemTime> {
+    u64::try_from(secs)
+        .ok()
+        .and_then(|secs| UNIX_EPOCH.checked_add(Duration::from_secs(secs)))
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid timestamp {secs}"),
+            )
+        })
+}
+
+/// Copy a C string owned by `libhdfs`, replacing invalid UTF-8.
+unsafe fn lossy_string(s: *const c_char) -> String {
+    if s.is_null() {
+        return String::new();
+    }
+    CStr::from_ptr(s).to_string_lossy().into_owned()
+}
@@ -0,0 +1,72 @@
+//! Safe wrappers around the raw `libhdfs` bindings.
+//!
+//! The raw functions re-exported at the crate root are still the primary
+//! interface of `hdfs-sys`. This module only covers the patterns that are easy
+//! to get wrong by hand, like freeing buffers and handles exactly once.
+//!
+//! All errors are reported as [`std::io::Error`], built from the `errno` that
+//! `libhdfs` sets on failure. Some failures are detected more precisely and
+//! carry an [`HdfsError`] instead.
+//!
+//! Enable the `async` feature for `AsyncFilesystem` and `AsyncFile`, which
+//! run the blocking calls on tokio's blocking threads.
+
+use std::ffi::CString;
+use std::io;
+
+#[cfg(feature = "async")]
+mod async_fs;
+#[cfg(feature = "async")]
+pub use async_fs::{AsyncFile, AsyncFilesystem, ReadDirStream};
+mod builder;
+pub use builder::ConnectBuilder;
+mod cached;
+pub use cached::CachedFs;
+mod config;
+pub use config::Config;
+pub mod error;
+pub use error::{to_errno, HdfsError};
+mod file;
+pub use file::{File, PreadFile};
+mod fs;
+#[cfg(feature = "hdfs_2_7")]
+pub use fs::TruncateResult;
+pub use fs::{Filesystem, FsStats};
+#[cfg(feature = "jni-interop")]
+mod jni;
+#[cfg(all(feature = "jni-interop", feature = "hdfs_3_0"))]
+pub use jni::detach_current_thread;
+#[cfg(feature = "jni-interop")]
+pub use jni::{current_jni_env, JNIEnv};
+mod metadata;
+pub use metadata::Metadata;
+mod open_options;
+pub use open_options::OpenOptions;
+mod path;
+pub use path::normalize_path;
+mod permissions;
+pub use permissions::Permissions;
+mod pool;
+pub use pool::FsPool;
+mod read_dir;
+pub use read_dir::ReadDir;
+mod retry;
+pub use retry::RetryReader;
+#[cfg(not(feature = "no-jvm"))]
+mod runtime;
+#[cfg(not(feature = "no-jvm"))]
+pub use runtime::{add_jvm_to_dll_path, init_classpath_from_hadoop_home};
+mod walk;
+pub use walk::Walk;
+#[cfg(feature = "hdfs_2_3")]
+mod zero_copy;
+#[cfg(feature = "hdfs_2_3")]
+pub use zero_copy::{ByteBufferPool, RzOptions, RzOptionsBuilder, ZeroCopyBuffer};
+
+#[cfg(test)]
+mod tests;
+
+/// Convert a rust string into a `CString`, rejecting interior nul bytes.
+fn to_cstring(s: &str) -> io::Result<CString> {
+    CString::new(s).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+}
@@ -0,0 +1,84 @@
+use std::io;
+use std::os::raw::{c_int, c_short};
+
+use super::fs::O_WRONLY;
+
+/// Options of [`Filesystem::open_with`](super::Filesystem::open_with), passed
+/// to `hdfsOpenFile`.
+///
+/// Following `libhdfs`, `0` means the configured default for all sizes:
+/// `io.file.buffer.size` for the buffer size, `dfs.replication` for the
+/// replication and `dfs.blocksize` for the block size.
+///
+/// ```no_run
+/// use hdfs_sys::safe::{Filesystem, OpenOptions};
+///
+/// let fs = Filesystem::connect("default", 0)?;
+/// let opts = OpenOptions::new().write(true).buffer_size(64 * 1024).replication(2);
+/// let f = fs.open_with("/tmp/file", &opts)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct OpenOptions {
+    write: bool,
+    buffer_size: u32,
+    replication: u16,
+    block_size: u64,
+}
+
+impl OpenOptions {
+    /// Create options to open a file for reading with all defaults.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open the file for writing, truncating it if it already exists.
+    pub fn write(mut self, write: bool) -> Self {
+        self.write = write;
+        self
+    }
+
+    /// Set the size of the stream buffer, `0` means `io.file.buffer.size`.
+    pub fn buffer_size(mut self, size: u32) -> Self {
+        self.buffer_size = size;
+        self
+    }
+
+    /// Set the replication of the created file, `0` means `dfs.replication`.
+    ///
+    /// Only valid for writing.
+    pub fn replication(mut self, replication: u16) -> Self {
+        self.replication = replication;
+        self
+    }
+
+    /// Set the block size of the created file, `0` means `dfs.blocksize`.
+    ///
+    /// Only valid for writing.
+    pub fn block_size(mut self, size: u64) -> Self {
+        self.block_size = size;
+        self
+    }
+
+    /// Validate and convert into the arguments of `hdfsOpenFile`.
+    pub(crate) fn to_raw(&self) -> io::Result<(c_int, c_int, c_short, i32)> {
+        if !self.write && (self.replication != 0 || self.block_size != 0) {
+            return Err(invalid_input(
+                "replication and block size are only valid for writing",
+            ));
+        }
+        let flags = if self.write { O_WRONLY } else { 0 };
+        let buffer_size = c_int::try_from(self.buffer_size)
+            .map_err(|_| invalid_input("buffer size is too large"))?;
+        let replication = c_short::try_from(self.replication)
+            .map_err(|_| invalid_input("replication is too large"))?;
+        let block_size =
+            i32::try_from(self.block_size).map_err(|_| invalid_input("block size is too large"))?;
+
+        Ok((flags, buffer_size, replication, block_size))
+    }
+}
+
+fn invalid_input(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
@@ -0,0 +1,75 @@
+/// Normalize a hdfs path lexically.
+///
+/// - Duplicate slashes are collapsed, `/tmp//a` becomes `/tmp/a`.
+/// - Trailing slashes are removed except for the root, `/tmp/` becomes `/tmp`.
+/// - `.` segments are removed, `..` removes the segment before it. `..` at
+///   the root stays at the root, leading `..` of a relative path are kept.
+/// - A `scheme://authority` prefix like `hdfs://namenode:9000` is kept as is.
+///
+/// This only looks at the string, no symlink is resolved. Classic hdfs has
+/// no symlinks enabled, but with them `a/link/..` may point elsewhere than
+/// `a`. An empty path becomes `.`.
+///
+/// ```
+/// use hdfs_sys::safe::normalize_path;
+///
+/// assert_eq!(normalize_path("/tmp//a/./b/../c/"), "/tmp/a/c");
+/// assert_eq!(normalize_path("hdfs://nn:9000//tmp/.."), "hdfs://nn:9000/");
+/// ```
+pub fn normalize_path(path: &str) -> String {
+    let (prefix, path) = match path.find("://") {
+        Some(i) => {
+            let rest = &path[i + 3..];
+            let end = rest.find('/').unwrap_or(rest.len());
+            path.split_at(i + 3 + end)
+        }
+        None => ("", path),
+    };
+    let absolute = !prefix.is_empty() || path.starts_with('/');
+
+    let mut segments: Vec<&str> = Vec::new();
+    for seg in path.split('/') {
+        match seg {
+            "" | "." => {}
+            ".." => match segments.last() {
+                Some(&last) if last != ".." => {
+                    segments.pop();
+                }
+                // Nothing above the root.
+                _ if absolute => {}
+                _ => segments.push(".."),
+            },
+            seg => segments.push(seg),
+        }
+    }
+
+    let joined = segments.join("/");
+    match (absolute, joined.is_empty()) {
+        (true, _) => format!("{prefix}/{joined}"),
+        (false, true) => ".".to_string(),
+        (false, false) => joined,
+    }
+}
+
+/// Get the parent dir of `path` lexically, `.` for a relative file name.
+///
+/// The parent of a top level entry is the root, like `hdfs://nn:9000/`
+/// for `hdfs://nn:9000/a`.
+pub(crate) fn parent_dir(path: &str) -> String {
+    let path = path.trim_end_matches('/');
+    let (prefix, path) = match path.find("://") {
+        Some(i) => {
+            let rest = &path[i + 3..];
+            let end = rest.find('/').unwrap_or(rest.len());
+            path.split_at(i + 3 + end)
+        }
+        None => ("", path),
+    };
+
+    match path.rsplit_once('/') {
+        Some(("", _)) => format!("{prefix}/"),
+        Some((parent, _)) => format!("{prefix}{parent}"),
+        None if prefix.is_empty() => ".".to_string(),
+        None => format!("{prefix}/"),
+    }
+}
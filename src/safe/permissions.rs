@@ -0,0 +1,124 @@
+use std::fmt;
+
+/// Permission bits of a file or directory, like `0o755`.
+///
+/// hdfs uses POSIX style modes: read, write and execute bits for the
+/// owner, the group and others, plus the sticky bit. There are no setuid
+/// or setgid bits.
+///
+/// Displayed like `ls -l`, such as `rwxr-xr-x`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Permissions {
+    mode: u16,
+}
+
+impl Permissions {
+    /// Create permissions from a mode like `0o644`, bits above `0o1777`
+    /// are dropped.
+    pub fn from_mode(mode: u16) -> Permissions {
+        Permissions {
+            mode: mode & 0o1777,
+        }
+    }
+
+    /// Get the mode of the permissions, like `0o644`.
+    pub fn mode(&self) -> u16 {
+        self.mode
+    }
+
+    /// Whether nobody can write, like [`std::fs::Permissions::readonly`].
+    pub fn readonly(&self) -> bool {
+        self.mode & 0o222 == 0
+    }
+
+    /// Whether the sticky bit is set, only the owner can delete entries of
+    /// a sticky directory like `/tmp`.
+    pub fn sticky(&self) -> bool {
+        self.mode & 0o1000 != 0
+    }
+
+    /// Whether the owner can read.
+    pub fn owner_read(&self) -> bool {
+        self.mode & 0o400 != 0
+    }
+
+    /// Whether the owner can write.
+    pub fn owner_write(&self) -> bool {
+        self.mode & 0o200 != 0
+    }
+
+    /// Whether the owner can execute, or list for a directory.
+    pub fn owner_execute(&self) -> bool {
+        self.mode & 0o100 != 0
+    }
+
+    /// Whether the group can read.
+    pub fn group_read(&self) -> bool {
+        self.mode & 0o040 != 0
+    }
+
+    /// Whether the group can write.
+    pub fn group_write(&self) -> bool {
+        self.mode & 0o020 != 0
+    }
+
+    /// Whether the group can execute, or list for a directory.
+    pub fn group_execute(&self) -> bool {
+        self.mode & 0o010 != 0
+    }
+
+    /// Whether others can read.
+    pub fn other_read(&self) -> bool {
+        self.mode & 0o004 != 0
+    }
+
+    /// Whether others can write.
+    pub fn other_write(&self) -> bool {
+        self.mode & 0o002 != 0
+    }
+
+    /// Whether others can execute, or list for a directory.
+    pub fn other_execute(&self) -> bool {
+        self.mode & 0o001 != 0
+    }
+}
+
+impl fmt::Display for Permissions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bits = [
+            (0o400, 'r'),
+            (0o200, 'w'),
+            (0o100, 'x'),
+            (0o040, 'r'),
+            (0o020, 'w'),
+            (0o010, 'x'),
+            (0o004, 'r'),
+            (0o002, 'w'),
+            (0o001, 'x'),
+        ];
+        for (i, (bit, c)) in bits.into_iter().enumerate() {
+            let set = self.mode & bit != 0;
+            let c = match (i, self.sticky()) {
+                // The sticky bit replaces the last `x`, like `ls -l`.
+                (8, true) if set => 't',
+                (8, true) => 'T',
+                _ if set => c,
+                _ => '-',
+            };
+            write!(f, "{c}")?;
+        }
+        Ok(())
+    }
+}
+
+impl From<u16> for Permissions {
+    fn from(mode: u16) -> Permissions {
+        Permissions::from_mode(mode)
+    }
+}
+
+impl From<Permissions> for u16 {
+    fn from(perm: Permissions) -> u16 {
+        perm.mode()
+    }
+}
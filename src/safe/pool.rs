@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+use std::io;
+use std::sync::Mutex;
+use std::thread::{self, ThreadId};
+
+use super::{ConnectBuilder, Filesystem};
+
+/// A pool of [`Filesystem`]s, one per thread, for worker threads.
+///
+/// `hdfsFS` itself is thread safe, but `libhdfs` attaches every calling
+/// thread to the JVM and keeps its JNI env in a thread local, and a
+/// `hdfsFile` must never be used by two threads at the same time. Giving
+/// each worker its own java `FileSystem` keeps the threads confined: no
+/// client or stream state is shared between them, and a broken connection
+/// only affects the thread using it.
+///
+/// Each thread gets a connection made by the stored builder with
+/// [`ConnectBuilder::force_new_instance`] on the first [`FsPool::get`].
+///
+/// ```no_run
+/// use hdfs_sys::safe::{ConnectBuilder, FsPool};
+///
+/// let pool = FsPool::new(ConnectBuilder::new("default"));
+/// std::thread::scope(|s| {
+///     for _ in 0..4 {
+///         s.spawn(|| pool.get()?.check());
+///     }
+/// });
+/// ```
+#[derive(Debug)]
+pub struct FsPool {
+    builder: ConnectBuilder,
+    handles: Mutex<HashMap<ThreadId, Filesystem>>,
+}
+
+impl FsPool {
+    /// Create an empty pool connecting with `builder`.
+    pub fn new(builder: ConnectBuilder) -> FsPool {
+        FsPool {
+            builder: builder.force_new_instance(true),
+            handles: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Get the [`Filesystem`] of the current thread, connecting on first use.
+    ///
+    /// A failed connect is not cached, the next call tries again.
+    pub fn get(&self) -> io::Result<Filesystem> {
+        let id = thread::current().id();
+        if let Some(fs) = self.lock().get(&id) {
+            return Ok(fs.clone());
+        }
+
+        // Connect without holding the lock, it may take a while.
+        let fs = self.builder.connect()?;
+        Ok(self.lock().entry(id).or_insert(fs).clone())
+    }
+
+    /// Drop the [`Filesystem`] of the current thread, like before the thread
+    /// exits.
+    pub fn remove(&self) {
+        self.lock().remove(&thread::current().id());
+    }
+
+    /// Number of threads with a [`Filesystem`] in this pool.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Whether no thread has a [`Filesystem`] in this pool.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<ThreadId, Filesystem>> {
+        // The map is always consistent, even if a thread panicked with it.
+        self.handles.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// Dropping the pool drops all its [`Filesystem`]s. A connection is
+/// disconnected once the handles returned by [`FsPool::get`] and the files
+/// opened from them are dropped too.
+impl Drop for FsPool {
+    fn drop(&mut self) {
+        self.lock().clear();
+    }
+}
@@ -0,0 +1,55 @@
+use std::iter::{Filter, FusedIterator};
+use std::vec;
+
+use super::Metadata;
+
+/// Iterator over the entries of a directory, returned by
+/// [`Filesystem::read_dir`](super::Filesystem::read_dir).
+///
+/// The directory is listed by a single `hdfsListDirectory`, and the info
+/// array is freed right after being copied into [`Metadata`]. The adapters
+/// below filter the copied entries without listing again.
+#[derive(Debug)]
+pub struct ReadDir {
+    entries: vec::IntoIter<Metadata>,
+}
+
+impl ReadDir {
+    pub(crate) fn new(entries: Vec<Metadata>) -> ReadDir {
+        ReadDir {
+            entries: entries.into_iter(),
+        }
+    }
+
+    /// Only yield regular files.
+    pub fn files(self) -> Filter<ReadDir, fn(&Metadata) -> bool> {
+        self.filter(|entry| entry.is_file())
+    }
+
+    /// Only yield directories.
+    pub fn dirs(self) -> Filter<ReadDir, fn(&Metadata) -> bool> {
+        self.filter(|entry| entry.is_dir())
+    }
+}
+
+impl Iterator for ReadDir {
+    type Item = Metadata;
+
+    fn next(&mut self) -> Option<Metadata> {
+        self.entries.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entries.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for ReadDir {
+    fn next_back(&mut self) -> Option<Metadata> {
+        self.entries.next_back()
+    }
+}
+
+impl ExactSizeIterator for ReadDir {}
+
+impl FusedIterator for ReadDir {}
@@ -0,0 +1,102 @@
+use std::io::{self, Read};
+
+use super::PreadFile;
+
+/// A reader that reopens the file and retries when a read fails.
+///
+/// Reads are issued with [`PreadFile::read_at`] at an offset tracked by the
+/// reader itself, so a retry after reopening resumes at exactly the same
+/// offset. This helps with transient datanode failures in the middle of a
+/// long read.
+///
+/// ```no_run
+/// use std::io::Read;
+///
+/// use hdfs_sys::safe::{Filesystem, RetryReader};
+///
+/// let fs = Filesystem::connect("default", 0)?;
+/// let path = "/tmp/file";
+/// let reopen_fs = fs.clone();
+/// let mut r = RetryReader::new(fs.open(path)?, move || reopen_fs.open(path)).max_retries(5);
+///
+/// let mut content = Vec::new();
+/// r.read_to_end(&mut content)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct RetryReader<F, O> {
+    file: F,
+    reopen: O,
+    max_retries: usize,
+    offset: u64,
+}
+
+impl<F, O> RetryReader<F, O>
+where
+    F: PreadFile,
+    O: FnMut() -> io::Result<F>,
+{
+    /// Create a new reader over `file` starting at offset `0`.
+    ///
+    /// `reopen` is called to get a new file after a failed read. By default,
+    /// a read is retried up to `3` times.
+    pub fn new(file: F, reopen: O) -> Self {
+        RetryReader {
+            file,
+            reopen,
+            max_retries: 3,
+            offset: 0,
+        }
+    }
+
+    /// Set how many times a failed read is retried before returning the error.
+    pub fn max_retries(mut self, n: usize) -> Self {
+        self.max_retries = n;
+        self
+    }
+
+    /// Offset of the next read.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Read at `offset`, reopening the file and retrying on errors.
+    ///
+    /// An error from `reopen` is returned immediately.
+    pub fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        let mut retries = 0;
+        loop {
+            match self.file.read_at(offset, buf) {
+                Ok(n) => return Ok(n),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) if retries >= self.max_retries => return Err(err),
+                Err(_) => {
+                    retries += 1;
+                    self.file = (self.reopen)()?;
+                }
+            }
+        }
+    }
+
+    /// Get a reference to the current file.
+    pub fn get_ref(&self) -> &F {
+        &self.file
+    }
+
+    /// Consume the reader, returning the current file.
+    pub fn into_inner(self) -> F {
+        self.file
+    }
+}
+
+impl<F, O> Read for RetryReader<F, O>
+where
+    F: PreadFile,
+    O: FnMut() -> io::Result<F>,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.read_at(self.offset, buf)?;
+        self.offset += n as u64;
+        Ok(n)
+    }
+}
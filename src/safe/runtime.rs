@@ -0,0 +1,103 @@
+use std::env;
+use std::ffi::OsStr;
+use std::io;
+use std::os::raw::c_void;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::ptr;
+
+extern "system" {
+    fn JNI_GetCreatedJavaVMs(vmBuf: *mut *mut c_void, bufLen: i32, nVMs: *mut i32) -> i32;
+}
+
+/// Whether the JVM has been started by `libhdfs` or anyone else.
+pub(crate) fn jvm_started() -> bool {
+    let mut vm = ptr::null_mut();
+    let mut n = 0;
+    let ret = unsafe { JNI_GetCreatedJavaVMs(&mut vm, 1, &mut n) };
+    ret == 0 && n > 0
+}
+
+/// Set `CLASSPATH` from `$HADOOP_HOME/bin/hadoop classpath --glob`.
+///
+/// The JVM reads `CLASSPATH` only once while starting, so this function must
+/// be called early in `main`, before any threads are spawned and before the
+/// first call into `libhdfs`. An error is returned if the JVM is already running.
+pub fn init_classpath_from_hadoop_home() -> io::Result<()> {
+    if jvm_started() {
+        return Err(io::Error::other(
+            "JVM has been started, CLASSPATH can't be changed anymore",
+        ));
+    }
+
+    let classpath = hadoop_classpath(env::var_os("HADOOP_HOME").as_deref())?;
+    env::set_var("CLASSPATH", classpath);
+    Ok(())
+}
+
+/// Prepend the dir of `jvm.dll` found at build time to `PATH` on windows,
+/// a no-op on other platforms.
+///
+/// Windows has no rpath, so `jvm.dll` must be on `PATH` when it's loaded,
+/// otherwise loading fails with `0xc0000135`, or `0xc0000139` if another
+/// JVM is found first. This must be called before any JNI call, which
+/// includes every call into `libhdfs`.
+///
+/// `jvm.dll` is imported at load time by default, which happens before
+/// `main`. So this only helps if the code linking `hdfs-sys` is loaded
+/// later, like a DLL loaded with `LoadLibrary`, or if the binary is linked
+/// with `/DELAYLOAD:jvm.dll`. Otherwise, set `PATH` before starting the
+/// process.
+pub fn add_jvm_to_dll_path() -> io::Result<()> {
+    if !cfg!(windows) {
+        return Ok(());
+    }
+    let Some(jvm_path) = option_env!("HDFS_SYS_JVM_PATH") else {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "no jvm dir is found at build time",
+        ));
+    };
+
+    let jvm_path = PathBuf::from(jvm_path);
+    let path = env::var_os("PATH").unwrap_or_default();
+    let mut paths: Vec<_> = env::split_paths(&path).collect();
+    if paths.first() == Some(&jvm_path) {
+        return Ok(());
+    }
+    paths.insert(0, jvm_path);
+    let path =
+        env::join_paths(paths).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    env::set_var("PATH", path);
+    Ok(())
+}
+
+/// Run `hadoop classpath --glob` under `hadoop_home`.
+pub(super) fn hadoop_classpath(hadoop_home: Option<&OsStr>) -> io::Result<String> {
+    let hadoop_home = hadoop_home
+        .filter(|v| !v.is_empty())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "HADOOP_HOME is not set"))?;
+
+    let hadoop = if cfg!(windows) {
+        "hadoop.cmd"
+    } else {
+        "hadoop"
+    };
+    let hadoop = Path::new(hadoop_home).join("bin").join(hadoop);
+    let output = Command::new(&hadoop)
+        .args(["classpath", "--glob"])
+        .output()
+        .map_err(|err| io::Error::new(err.kind(), format!("run {}: {err}", hadoop.display())))?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "run {}: {}: {}",
+            hadoop.display(),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    String::from_utf8(output.stdout)
+        .map(|v| v.trim().to_string())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
@@ -0,0 +1,1856 @@
+use std::cell::Cell;
+use std::env;
+use std::io;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use super::*;
+use crate::*;
+
+/// Get the cluster set by `HDFS_TEST_NAMENODE` and `HDFS_TEST_PORT`.
+///
+/// Returns `None` without the `cluster-tests` feature so that the test is
+/// skipped, a missing `HDFS_TEST_NAMENODE` fails the test otherwise.
+fn cluster_addr() -> Option<(String, u16)> {
+    if !cfg!(feature = "cluster-tests") {
+        return None;
+    }
+    let namenode =
+        env::var("HDFS_TEST_NAMENODE").expect("cluster-tests requires HDFS_TEST_NAMENODE");
+    let port = env::var("HDFS_TEST_PORT")
+        .ok()
+        .map(|v| v.parse().expect("HDFS_TEST_PORT must be a port"))
+        .unwrap_or(0);
+
+    Some((namenode, port))
+}
+
+/// Connect to the cluster of [`cluster_addr`], the connection is shared by
+/// all tests.
+fn cluster() -> Option<Filesystem> {
+    static FS: OnceLock<Filesystem> = OnceLock::new();
+
+    let (namenode, port) = cluster_addr()?;
+    let fs =
+        FS.get_or_init(|| Filesystem::connect(&namenode, port).expect("connect to test cluster"));
+    Some(fs.clone())
+}
+
+/// A directory for a single test, deleted recursively on drop.
+struct TempDir {
+    fs: Filesystem,
+    path: String,
+}
+
+impl TempDir {
+    /// Create `/tmp/hdfs-sys-test-{name}`, removing the leftover of a
+    /// previous run first.
+    fn new(fs: &Filesystem, name: &str) -> TempDir {
+        let dir = TempDir {
+            fs: fs.clone(),
+            path: format!("/tmp/hdfs-sys-test-{name}"),
+        };
+        dir.delete();
+        let p = to_cstring(&dir.path).unwrap();
+        let ret = unsafe { hdfsCreateDirectory(fs.as_raw(), p.as_ptr()) };
+        assert_eq!(ret, 0, "create {}", dir.path);
+        dir
+    }
+
+    /// Get the path of `name` in this directory.
+    fn join(&self, name: &str) -> String {
+        format!("{}/{name}", self.path)
+    }
+
+    fn delete(&self) {
+        let p = to_cstring(&self.path).unwrap();
+        // Fails if the directory doesn't exist, which is fine.
+        unsafe { hdfsDelete(self.fs.as_raw(), p.as_ptr(), 1) };
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        self.delete();
+    }
+}
+
+/// Write `data` into `path` with the raw API.
+fn write_file(fs: &Filesystem, path: &str, data: &[u8]) {
+    write_file_with_block_size(fs, path, data, 0)
+}
+
+/// Write `data` into `path` with the raw API, `0` means the default block size.
+fn write_file_with_block_size(fs: &Filesystem, path: &str, data: &[u8], block_size: i32) {
+    let p = to_cstring(path).unwrap();
+    unsafe {
+        let f = hdfsOpenFile(fs.as_raw(), p.as_ptr(), 1, 0, 0, block_size);
+        assert!(!f.is_null(), "open {path} for write");
+        let n = hdfsWrite(fs.as_raw(), f, data.as_ptr().cast(), data.len() as i32);
+        assert_eq!(n, data.len() as i32);
+        assert_eq!(hdfsCloseFile(fs.as_raw(), f), 0);
+    }
+}
+
+#[test]
+fn test_to_cstring_rejects_nul() {
+    let err = to_cstring("/tmp/a\0b").unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn test_to_errno() {
+    let errno = |kind| to_errno(&io::Error::from(kind));
+    assert_eq!(errno(io::ErrorKind::NotFound), 2);
+    assert_eq!(errno(io::ErrorKind::PermissionDenied), 13);
+    assert_eq!(errno(io::ErrorKind::AlreadyExists), 17);
+    assert_eq!(errno(io::ErrorKind::TimedOut), 5);
+    assert_eq!(errno(io::ErrorKind::Other), 5);
+
+    // `errno` set by `libhdfs` is kept, like `EINTERNAL`.
+    assert_eq!(to_errno(&io::Error::from_raw_os_error(255)), 255);
+    assert_eq!(to_errno(&HdfsError::ClasspathMissing.into()), 2);
+}
+
+#[test]
+fn test_hdfs_error_from_io() {
+    let err: io::Error = HdfsError::ClasspathMissing.into();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    assert!(err.to_string().contains("CLASSPATH"));
+    assert!(matches!(
+        HdfsError::from_io(&err),
+        Some(HdfsError::ClasspathMissing)
+    ));
+
+    let err = io::Error::from_raw_os_error(2);
+    assert!(HdfsError::from_io(&err).is_none());
+}
+
+#[test]
+#[cfg(feature = "hdfs_3_0")]
+fn test_is_class_missing() {
+    assert!(error::is_class_missing(
+        "java.lang.ClassNotFoundException: org.apache.hadoop.fs.FileSystem"
+    ));
+    assert!(error::is_class_missing(
+        "NoClassDefFoundError: org/apache/hadoop/conf/Configuration"
+    ));
+    assert!(!error::is_class_missing(
+        "java.net.ConnectException: Connection refused"
+    ));
+}
+
+#[test]
+#[cfg(feature = "hdfs_3_0")]
+fn test_is_access_denied() {
+    assert!(error::is_access_denied(
+        "org.apache.hadoop.security.authorize.AuthorizationException: \
+         User: hdfs is not allowed to impersonate alice"
+    ));
+    assert!(error::is_access_denied(
+        "org.apache.hadoop.security.AccessControlException: Permission denied"
+    ));
+    assert!(!error::is_access_denied(
+        "java.net.ConnectException: Connection refused"
+    ));
+}
+
+#[test]
+fn test_impersonation_denied() {
+    // `hdfsConnectAsUser` is bound and links.
+    let _ = hdfsConnectAsUser;
+
+    let err: io::Error = HdfsError::ImpersonationDenied {
+        user: "alice".to_string(),
+    }
+    .into();
+    assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+    assert!(err.to_string().contains("alice"));
+    assert!(matches!(
+        HdfsError::from_io(&err),
+        Some(HdfsError::ImpersonationDenied { user }) if user == "alice"
+    ));
+}
+
+#[test]
+#[cfg(feature = "hdfs_3_0")]
+fn test_connect_exception_error() {
+    let cause = "AuthorizationException: User: hdfs is not allowed to impersonate alice";
+    let builder = ConnectBuilder::new("default");
+    assert!(builder.cause_error(cause).is_none());
+
+    let builder = builder.user("alice");
+    assert!(matches!(
+        builder.cause_error(cause),
+        Some(HdfsError::ImpersonationDenied { user }) if user == "alice"
+    ));
+    assert!(matches!(
+        builder.cause_error("NoClassDefFoundError: org/apache/hadoop/conf/Configuration"),
+        Some(HdfsError::ClasspathMissing)
+    ));
+    assert!(builder
+        .cause_error("ConnectException: Connection refused")
+        .is_none());
+
+    // The exception of `EINTERNAL` is never read, it may be freed.
+    assert!(builder.exception_error(255).is_none());
+}
+
+#[test]
+#[cfg(feature = "hdfs_2_3")]
+fn test_byte_buffer_pool_class() {
+    assert_eq!(
+        ByteBufferPool::ELASTIC.class().to_bytes_with_nul(),
+        ELASTIC_BYTE_BUFFER_POOL_CLASS
+    );
+    for pool in [
+        ByteBufferPool::ELASTIC,
+        ByteBufferPool::WEAK_REFERENCED_ELASTIC,
+    ] {
+        // JNI expects class names separated by slashes.
+        let class = pool.class().to_str().unwrap();
+        assert!(class.starts_with("org/apache/hadoop/io/"));
+        assert!(!class.contains('.'));
+    }
+}
+
+#[test]
+#[cfg(feature = "hdfs_2_3")]
+fn test_read_zero_copy() {
+    let Some(fs) = cluster() else { return };
+
+    let path = "/tmp/hdfs-sys-test-read-zero-copy";
+    let data = b"Hello, World!";
+    write_file(&fs, path, data);
+
+    let mut f = fs.open(path).unwrap();
+    let opts = RzOptionsBuilder::new().skip_checksum(true).build().unwrap();
+    let mut content = Vec::new();
+    loop {
+        let buf = f.read_zero_copy_with(&opts, 4).unwrap();
+        if buf.is_empty() {
+            break;
+        }
+        content.extend_from_slice(buf.as_slice());
+    }
+    assert_eq!(content, data);
+
+    // Zero-copy reads share the stream position with normal reads.
+    let mut rest = Vec::new();
+    f.read_to_end(&mut rest).unwrap();
+    assert!(rest.is_empty());
+}
+
+#[test]
+fn test_block_hosts() {
+    let Some(fs) = cluster() else { return };
+
+    let path = "/tmp/hdfs-sys-test-block-hosts";
+    // 1MiB is the minimal block size allowed by namenode by default.
+    let block_size = 1024 * 1024;
+    let data = vec![b'a'; 3 * block_size as usize];
+    write_file_with_block_size(&fs, path, &data, block_size);
+
+    let hosts = fs.block_hosts(path, 0, data.len() as u64).unwrap();
+    assert!(!hosts.is_empty());
+    assert!(hosts.iter().all(|names| !names.is_empty()));
+
+    let hosts = fs.block_hosts(path, 0, 0).unwrap();
+    assert!(hosts.is_empty());
+
+    // Would be negative for `hdfsGetHosts`.
+    let err = fs.block_hosts(path, u64::MAX, 1).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    let err = fs.block_hosts(path, 0, u64::MAX).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn test_short_host() {
+    assert_eq!(fs::short_host("DN1.Example.com"), "dn1");
+    assert_eq!(fs::short_host("dn1"), "dn1");
+    assert_eq!(fs::short_host("10.0.0.1"), "10.0.0.1");
+    assert_eq!(fs::short_host("::1"), "::1");
+}
+
+#[test]
+fn test_is_local() {
+    let Some(fs) = cluster() else { return };
+
+    let dir = TempDir::new(&fs, "is-local");
+    let path = &dir.join("file");
+    write_file(&fs, path, b"Hello, World!");
+
+    let host = fs.block_hosts(path, 0, 13).unwrap()[0][0].clone();
+    assert!(fs.is_local(path, 0, 13, &host).unwrap());
+    assert!(fs.is_local(path, 0, 13, &host.to_uppercase()).unwrap());
+    assert!(!fs.is_local(path, 0, 13, "hdfs-sys-no-such-host").unwrap());
+    assert!(!fs.is_local(path, 0, 0, &host).unwrap());
+}
+
+#[test]
+fn test_connect_timeout_ms() {
+    let builder = ConnectBuilder::new("hdfs://127.0.0.1:9000").connect_timeout_ms(3000);
+
+    assert_eq!(builder.get_conf("ipc.client.connect.timeout"), Some("3000"));
+    assert_eq!(
+        builder.get_conf("ipc.client.connect.max.retries.on.timeouts"),
+        Some("0")
+    );
+}
+
+#[test]
+fn test_namenode() {
+    let uri = |namenode: &str, port: u16| {
+        ConnectBuilder::new("default")
+            .namenode(namenode)
+            .port(port)
+            .uri()
+    };
+
+    assert_eq!(uri("default", 9000).unwrap(), None);
+    assert_eq!(
+        uri("nn1.example.com", 0).unwrap().as_deref(),
+        Some("hdfs://nn1.example.com")
+    );
+    assert_eq!(
+        uri("nn1.example.com", 9000).unwrap().as_deref(),
+        Some("hdfs://nn1.example.com:9000")
+    );
+    assert_eq!(
+        uri("127.0.0.1:9000", 0).unwrap().as_deref(),
+        Some("hdfs://127.0.0.1:9000")
+    );
+    assert_eq!(
+        uri("mycluster", 0).unwrap().as_deref(),
+        Some("hdfs://mycluster")
+    );
+    assert_eq!(
+        uri("hdfs://127.0.0.1:9000", 0).unwrap().as_deref(),
+        Some("hdfs://127.0.0.1:9000")
+    );
+    assert_eq!(
+        uri("viewfs://cluster", 8020).unwrap().as_deref(),
+        Some("viewfs://cluster:8020")
+    );
+
+    let err = uri("127.0.0.1:9000", 9000).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+    // Rejected before the JVM is started.
+    let builder = ConnectBuilder::new("hdfs://127.0.0.1:9000").namenode("");
+    assert_eq!(builder.get_namenode(), "");
+    let err = builder.connect().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn test_from_url() {
+    let namenode = |url: &str| {
+        let builder = ConnectBuilder::from_url(url).unwrap();
+        builder.get_namenode().to_string()
+    };
+
+    assert_eq!(namenode("hdfs://127.0.0.1:9000"), "hdfs://127.0.0.1:9000");
+    assert_eq!(namenode("hdfs://mycluster/"), "hdfs://mycluster");
+    assert_eq!(namenode("viewfs://cluster"), "viewfs://cluster");
+    assert_eq!(namenode("ViewFS://cluster/"), "viewfs://cluster");
+    assert_eq!(namenode("viewfs:///"), "viewfs:///");
+
+    // The mount table is resolved by hadoop while connecting, not parsed.
+    let builder = ConnectBuilder::from_url("viewfs://cluster").unwrap();
+    assert_eq!(builder.uri().unwrap().as_deref(), Some("viewfs://cluster"));
+
+    for url in [
+        "127.0.0.1:9000",
+        "file:///tmp",
+        "hdfs:///",
+        "viewfs://cluster/tmp",
+    ] {
+        let err = ConnectBuilder::from_url(url).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{url}");
+    }
+}
+
+#[test]
+#[cfg(windows)]
+#[cfg(not(feature = "no-jvm"))]
+fn test_add_jvm_to_dll_path() {
+    let jvm_path = env!("HDFS_SYS_JVM_PATH");
+
+    add_jvm_to_dll_path().unwrap();
+    add_jvm_to_dll_path().unwrap();
+    let path = env::var_os("PATH").unwrap();
+    let paths: Vec<_> = env::split_paths(&path).collect();
+    assert_eq!(paths[0], std::path::Path::new(jvm_path));
+    assert_ne!(paths.get(1), Some(&paths[0]));
+}
+
+#[test]
+#[cfg(not(feature = "no-jvm"))]
+fn test_hadoop_classpath_without_hadoop_home() {
+    let err = runtime::hadoop_classpath(None).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+
+    let err = runtime::hadoop_classpath(Some("".as_ref())).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+}
+
+#[test]
+#[cfg(not(feature = "no-jvm"))]
+fn test_hadoop_classpath_with_invalid_hadoop_home() {
+    let err = runtime::hadoop_classpath(Some("/path/to/nowhere".as_ref())).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+}
+
+#[test]
+fn test_seek() {
+    let Some(fs) = cluster() else { return };
+
+    let path = "/tmp/hdfs-sys-test-seek";
+    write_file(&fs, path, b"Hello, World!");
+
+    let mut f = fs.open(path).unwrap();
+    let mut buf = [0; 5];
+    assert_eq!(f.seek(SeekFrom::Start(7)).unwrap(), 7);
+    f.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"World");
+    assert_eq!(f.seek(SeekFrom::Current(-12)).unwrap(), 0);
+    assert_eq!(f.seek(SeekFrom::End(-6)).unwrap(), 7);
+    assert!(f.seek(SeekFrom::Current(-8)).is_err());
+}
+
+#[test]
+fn test_seek_write_stream() {
+    let Some(fs) = cluster() else { return };
+
+    let mut f = fs.create("/tmp/hdfs-sys-test-seek-write-stream").unwrap();
+    f.write_all(b"Hello").unwrap();
+
+    let err = f.seek(SeekFrom::Start(0)).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+}
+
+#[test]
+fn test_close() {
+    let Some(fs) = cluster() else { return };
+
+    let path = "/tmp/hdfs-sys-test-close";
+    let mut f = fs.create(path).unwrap();
+    f.write_all(b"Hello, World!").unwrap();
+    f.close().unwrap();
+
+    let mut content = String::new();
+    let mut f = fs.open(path).unwrap();
+    f.read_to_string(&mut content).unwrap();
+    assert_eq!(content, "Hello, World!");
+    f.close().unwrap();
+}
+
+#[test]
+fn test_disconnect() {
+    let Some((namenode, port)) = cluster_addr() else {
+        return;
+    };
+    // Don't close the cached `FileSystem` used by other tests.
+    let fs = ConnectBuilder::new(&namenode)
+        .port(port)
+        .force_new_instance(true)
+        .connect()
+        .expect("connect to test cluster");
+
+    let other = fs.clone();
+    assert!(other.disconnect().is_err());
+
+    fs.check().unwrap();
+    fs.disconnect().unwrap();
+}
+
+#[test]
+fn test_working_dir() {
+    let Some((namenode, port)) = cluster_addr() else {
+        return;
+    };
+    // Don't change the working dir of the cached `FileSystem` used by
+    // other tests.
+    let fs = ConnectBuilder::new(&namenode)
+        .port(port)
+        .force_new_instance(true)
+        .connect()
+        .expect("connect to test cluster");
+
+    fs.set_working_dir("/tmp").unwrap();
+    assert!(fs.working_dir().unwrap().ends_with("/tmp"));
+
+    let mut f = fs.create("hdfs-sys-test-working-dir").unwrap();
+    f.write_all(b"Hello, World!").unwrap();
+    f.close().unwrap();
+
+    let abs = fs.metadata("/tmp/hdfs-sys-test-working-dir").unwrap();
+    let rel = fs.metadata("hdfs-sys-test-working-dir").unwrap();
+    assert_eq!(rel.path(), abs.path());
+    assert_eq!(rel.len(), 13);
+}
+
+#[test]
+fn test_read_timeout_ms() {
+    let builder = ConnectBuilder::new("hdfs://127.0.0.1:9000").read_timeout_ms(10000);
+
+    assert_eq!(builder.get_conf("dfs.client.socket-timeout"), Some("10000"));
+    assert_eq!(
+        builder.get_conf("dfs.datanode.socket.write.timeout"),
+        Some("10000")
+    );
+}
+
+#[test]
+fn test_fs_pool() {
+    let Some((namenode, port)) = cluster_addr() else {
+        return;
+    };
+    let pool = FsPool::new(ConnectBuilder::new(&namenode).port(port));
+
+    std::thread::scope(|s| {
+        for i in 0..4 {
+            let pool = &pool;
+            s.spawn(move || {
+                let fs = pool.get().unwrap();
+                assert!(std::sync::Arc::ptr_eq(&fs.conn, &pool.get().unwrap().conn));
+
+                let path = format!("/tmp/hdfs-sys-test-fs-pool-{i}");
+                write_file(&fs, &path, b"Hello, World!");
+                assert_eq!(fs.metadata(&path).unwrap().len(), 13);
+            });
+        }
+    });
+    assert_eq!(pool.len(), 4);
+}
+
+/// Resident memory and thread count of this process, only on linux.
+fn process_usage() -> Option<(u64, u64)> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let field = |name: &str| -> Option<u64> {
+        let line = status.lines().find(|l| l.starts_with(name))?;
+        line[name.len()..].split_whitespace().next()?.parse().ok()
+    };
+    Some((field("VmRSS:")? * 1024, field("Threads:")?))
+}
+
+#[test]
+fn test_connect_disconnect_cycles() {
+    let Some((namenode, port)) = cluster_addr() else {
+        return;
+    };
+    let builder = ConnectBuilder::new(&namenode)
+        .port(port)
+        .force_new_instance(true);
+    let cycle = || {
+        let fs = builder.connect().expect("connect to test cluster");
+        fs.check().unwrap();
+        fs.disconnect().unwrap();
+    };
+
+    // Warm up the JVM, the first connections load classes and start
+    // long-lived threads.
+    for _ in 0..100 {
+        cycle();
+    }
+    let before = process_usage();
+    for _ in 0..2000 {
+        cycle();
+    }
+
+    // Best effort, other tests run in parallel in the same process.
+    if let (Some((rss, threads)), Some((rss_after, threads_after))) = (before, process_usage()) {
+        assert!(
+            rss_after < rss + 256 * 1024 * 1024,
+            "rss grew from {rss} to {rss_after}"
+        );
+        assert!(
+            threads_after < threads + 64,
+            "threads grew from {threads} to {threads_after}"
+        );
+    }
+}
+
+#[test]
+fn test_connect_builder_confs() {
+    let confs = std::collections::HashMap::from([
+        ("dfs.replication".to_string(), "1".to_string()),
+        (
+            "dfs.client.use.datanode.hostname".to_string(),
+            "true".to_string(),
+        ),
+    ]);
+    let builder = ConnectBuilder::new("hdfs://127.0.0.1:9000")
+        .conf("dfs.replication", "3")
+        .confs(&confs);
+
+    assert_eq!(builder.get_conf("dfs.replication"), Some("1"));
+    assert_eq!(
+        builder.get_conf("dfs.client.use.datanode.hostname"),
+        Some("true")
+    );
+}
+
+#[test]
+fn test_connect_unreachable() {
+    let Some((namenode, _)) = cluster_addr() else {
+        return;
+    };
+
+    // Nothing listens on port 1.
+    let builder = ConnectBuilder::new(&namenode)
+        .port(1)
+        .force_new_instance(true)
+        .connect_timeout_ms(1000);
+    match builder.connect() {
+        Err(err) => {
+            let msg = err.to_string();
+            assert!(msg.contains(&format!("{namenode}:1")), "{msg}");
+            assert!(msg.contains("errno"), "{msg}");
+        }
+        // Namenode is not contacted until the first call.
+        Ok(fs) => assert!(fs.check().is_err()),
+    }
+}
+
+#[test]
+fn test_errno_name() {
+    assert_eq!(error::errno_name(2), Some("ENOENT"));
+    assert_eq!(error::errno_name(255), Some("EINTERNAL"));
+    assert_eq!(error::errno_name(0), None);
+}
+
+#[test]
+fn test_read_to_end_buffered() {
+    let Some(fs) = cluster() else { return };
+
+    let path = "/tmp/hdfs-sys-test-read-to-end-buffered";
+    let data: Vec<u8> = (0..3 * 1024 * 1024 + 7).map(|i| i as u8).collect();
+    write_file(&fs, path, &data);
+
+    for chunk in [0, 1000, 1024 * 1024] {
+        let mut f = fs.open(path).unwrap();
+        let mut buf = b"head".to_vec();
+        let n = f.read_to_end_buffered(&mut buf, chunk).unwrap();
+        assert_eq!(n, data.len());
+        assert_eq!(&buf[..4], b"head");
+        assert_eq!(&buf[4..], &data[..]);
+    }
+}
+
+#[cfg(feature = "metrics")]
+#[test]
+fn test_file_metrics() {
+    let Some(fs) = cluster() else { return };
+
+    let path = "/tmp/hdfs-sys-test-file-metrics";
+    let mut w = fs.create(path).unwrap();
+    w.write_all(b"Hello, World!").unwrap();
+    w.write_all(b" Again.").unwrap();
+    assert_eq!(w.bytes_written(), 20);
+    assert_eq!(w.bytes_read(), 0);
+    w.close().unwrap();
+
+    let mut r = fs.open(path).unwrap();
+    let mut buf = Vec::new();
+    r.read_to_end(&mut buf).unwrap();
+    assert_eq!(r.bytes_read(), 20);
+
+    let mut buf = [0; 5];
+    assert_eq!(r.read_at(7, &mut buf).unwrap(), 5);
+    assert_eq!(r.bytes_read(), 25);
+    assert_eq!(r.bytes_written(), 0);
+}
+
+/// Run under ASan with the `asan` feature to catch a double free.
+#[test]
+fn test_close_then_drop() {
+    let Some(fs) = cluster() else { return };
+
+    let dir = TempDir::new(&fs, "close-then-drop");
+    let path = &dir.join("file");
+    {
+        let mut f = fs.create(path).unwrap();
+        f.write_all(b"Hello, World!").unwrap();
+        // Consumes `f`, so it's not closed again at the end of the scope.
+        f.close().unwrap();
+    }
+    {
+        let f = fs.open(path).unwrap();
+        f.close().unwrap();
+    }
+    assert_eq!(fs.metadata(path).unwrap().len(), 13);
+}
+
+#[test]
+fn test_drop_flushes() {
+    let Some(fs) = cluster() else { return };
+
+    let path = "/tmp/hdfs-sys-test-drop-flushes";
+    let mut w = fs.create(path).unwrap();
+    w.write_all(b"Hello, ").unwrap();
+    w.write_all(b"World!").unwrap();
+    drop(w);
+
+    let mut buf = Vec::new();
+    fs.open(path).unwrap().read_to_end(&mut buf).unwrap();
+    assert_eq!(buf, b"Hello, World!");
+}
+
+#[test]
+fn test_create_synced() {
+    let Some(fs) = cluster() else { return };
+
+    let path = "/tmp/hdfs-sys-test-create-synced";
+    let mut w = fs.create_synced(path).unwrap();
+    let mut expected = Vec::new();
+    for i in 0..3 {
+        let data = format!("line {i}\n");
+        w.write_all(data.as_bytes()).unwrap();
+        expected.extend_from_slice(data.as_bytes());
+
+        // A reader opened while writing sees all synced data.
+        let mut r = fs.open(path).unwrap();
+        let mut buf = Vec::new();
+        r.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, expected);
+    }
+    w.close().unwrap();
+}
+
+#[test]
+fn test_set_times() {
+    let Some(fs) = cluster() else { return };
+
+    let path = "/tmp/hdfs-sys-test-set-times";
+    write_file(&fs, path, b"Hello, World!");
+
+    let mtime = std::time::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+    fs.set_times(path, Some(mtime), None).unwrap();
+    let meta = fs.metadata(path).unwrap();
+    assert_eq!(meta.modified().unwrap(), mtime);
+
+    let atime = std::time::UNIX_EPOCH + Duration::from_secs(1_500_000_000);
+    fs.set_times(path, None, Some(atime)).unwrap();
+    assert_eq!(fs.metadata(path).unwrap().modified().unwrap(), mtime);
+
+    let before_epoch = std::time::UNIX_EPOCH - Duration::from_secs(1);
+    let err = fs.set_times(path, Some(before_epoch), None).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn test_normalize_path() {
+    // Duplicate slashes.
+    assert_eq!(normalize_path("/tmp//a///b"), "/tmp/a/b");
+    // Trailing slashes, except the root.
+    assert_eq!(normalize_path("/tmp/a/"), "/tmp/a");
+    assert_eq!(normalize_path("/"), "/");
+    assert_eq!(normalize_path("//"), "/");
+    // `.` and `..`.
+    assert_eq!(normalize_path("/tmp/./a/../b"), "/tmp/b");
+    assert_eq!(normalize_path("/.."), "/");
+    assert_eq!(normalize_path("/tmp/../../a"), "/a");
+    // Relative paths.
+    assert_eq!(normalize_path("a/./b/"), "a/b");
+    assert_eq!(normalize_path("../a/../.."), "../..");
+    assert_eq!(normalize_path("a/.."), ".");
+    assert_eq!(normalize_path(""), ".");
+    // Uri prefix is kept.
+    assert_eq!(
+        normalize_path("hdfs://nn:9000//tmp/./a/"),
+        "hdfs://nn:9000/tmp/a"
+    );
+    assert_eq!(normalize_path("hdfs://nn:9000"), "hdfs://nn:9000/");
+    assert_eq!(normalize_path("hdfs://nn:9000/.."), "hdfs://nn:9000/");
+}
+
+#[test]
+fn test_parent_dir() {
+    use super::path::parent_dir;
+
+    assert_eq!(parent_dir("/tmp/a/b"), "/tmp/a");
+    assert_eq!(parent_dir("/tmp/a/"), "/tmp");
+    assert_eq!(parent_dir("/tmp"), "/");
+    assert_eq!(parent_dir("a/b"), "a");
+    assert_eq!(parent_dir("a"), ".");
+    assert_eq!(parent_dir("hdfs://nn:9000/tmp/a"), "hdfs://nn:9000/tmp");
+    assert_eq!(parent_dir("hdfs://nn:9000/a"), "hdfs://nn:9000/");
+}
+
+#[test]
+fn test_normalize_paths_on_connection() {
+    let Some((namenode, port)) = cluster_addr() else {
+        return;
+    };
+    let fs = ConnectBuilder::new(&namenode)
+        .port(port)
+        .normalize_paths(true)
+        .connect()
+        .expect("connect to test cluster");
+
+    write_file(&fs, "/tmp/hdfs-sys-test-normalize", b"Hello, World!");
+    assert_eq!(
+        fs.path("/tmp//x/../hdfs-sys-test-normalize/"),
+        "/tmp/hdfs-sys-test-normalize"
+    );
+    let meta = fs.metadata("/tmp//x/../hdfs-sys-test-normalize/").unwrap();
+    assert_eq!(meta.len(), 13);
+}
+
+#[test]
+fn test_permissions() {
+    let perm = Permissions::from_mode(0o755);
+    assert_eq!(perm.mode(), 0o755);
+    assert!(perm.owner_read() && perm.owner_write() && perm.owner_execute());
+    assert!(perm.group_read() && !perm.group_write() && perm.group_execute());
+    assert!(perm.other_read() && !perm.other_write() && perm.other_execute());
+    assert!(!perm.readonly());
+    assert_eq!(perm.to_string(), "rwxr-xr-x");
+
+    let perm = Permissions::from(0o644);
+    assert_eq!(u16::from(perm), 0o644);
+    assert!(perm.owner_read() && perm.owner_write() && !perm.owner_execute());
+    assert!(perm.group_read() && !perm.group_write() && !perm.group_execute());
+    assert!(perm.other_read() && !perm.other_write() && !perm.other_execute());
+    assert_eq!(perm.to_string(), "rw-r--r--");
+
+    assert!(Permissions::from_mode(0o444).readonly());
+    assert_eq!(Permissions::from_mode(0o1777).to_string(), "rwxrwxrwt");
+    assert_eq!(Permissions::from_mode(0o1770).to_string(), "rwxrwx--T");
+    assert_eq!(Permissions::from_mode(0o7777).mode(), 0o1777);
+
+    let mut info = unsafe { std::mem::zeroed::<hdfsFileInfo>() };
+    info.mPermissions = 0o640;
+    let meta = unsafe { Metadata::from_raw(&info) };
+    assert_eq!(meta.permissions(), Permissions::from_mode(0o640));
+}
+
+#[test]
+fn test_cached_fs() {
+    // A filesystem is needed to build the cache, but never used by the
+    // counting stat below.
+    let fs = Filesystem {
+        conn: std::sync::Arc::new(fs::Connection {
+            fs: std::ptr::null_mut(),
+            normalize_paths: false,
+        }),
+    };
+    let cached = CachedFs::new(fs, Duration::from_millis(200));
+
+    let calls = Cell::new(0);
+    let stat = |_: &str| {
+        calls.set(calls.get() + 1);
+        let mut info = unsafe { std::mem::zeroed::<hdfsFileInfo>() };
+        info.mSize = calls.get();
+        Ok(unsafe { Metadata::from_raw(&info) })
+    };
+
+    assert_eq!(cached.metadata_with("/a", stat).unwrap().len(), 1);
+    assert_eq!(cached.metadata_with("/a", stat).unwrap().len(), 1);
+    assert_eq!(calls.get(), 1);
+
+    // Paths are cached separately.
+    assert_eq!(cached.metadata_with("/b", stat).unwrap().len(), 2);
+    assert_eq!(calls.get(), 2);
+
+    cached.invalidate("/a");
+    assert_eq!(cached.metadata_with("/a", stat).unwrap().len(), 3);
+
+    // Expired entries are refreshed.
+    std::thread::sleep(Duration::from_millis(250));
+    assert_eq!(cached.metadata_with("/a", stat).unwrap().len(), 4);
+    assert_eq!(cached.metadata_with("/a", stat).unwrap().len(), 4);
+
+    // Errors are not cached.
+    cached.clear();
+    let err = cached
+        .metadata_with("/a", |_| Err(io::ErrorKind::NotFound.into()))
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    assert_eq!(cached.metadata_with("/a", stat).unwrap().len(), 5);
+}
+
+#[test]
+fn test_metadata() {
+    let Some(fs) = cluster() else { return };
+
+    let path = "/tmp/hdfs-sys-test-metadata";
+    write_file(&fs, path, b"Hello, World!");
+
+    let meta = fs.metadata(path).unwrap();
+    assert_eq!(meta.len(), 13);
+    assert!(meta.is_file());
+    assert!(!meta.is_dir());
+    assert!(meta.path().ends_with(path));
+    assert!(meta.modified().unwrap() > std::time::UNIX_EPOCH);
+
+    let meta = fs.metadata("/tmp").unwrap();
+    assert!(meta.is_dir());
+
+    let err = fs.metadata("/tmp/hdfs-sys-test-not-exist").unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+}
+
+#[test]
+fn test_metadata_owner() {
+    let Some((namenode, port)) = cluster_addr() else {
+        return;
+    };
+    let fs = ConnectBuilder::new(&namenode)
+        .port(port)
+        .user("hdfs-sys-owner")
+        .connect()
+        .expect("connect to test cluster");
+
+    let path = "/tmp/hdfs-sys-test-metadata-owner";
+    write_file(&fs, path, b"owner");
+
+    let meta = fs.metadata(path).unwrap();
+    assert_eq!(meta.owner(), "hdfs-sys-owner");
+    assert!(!meta.group().is_empty());
+}
+
+#[test]
+fn test_hadoop_user_name() {
+    let builder = ConnectBuilder::new("default");
+    assert_eq!(builder.clone().default_user(None).get_user(), None);
+    assert_eq!(builder.clone().default_user(Some("")).get_user(), None);
+    assert_eq!(
+        builder.clone().default_user(Some("bob")).get_user(),
+        Some("bob")
+    );
+    // Set on the builder, the config files are not read.
+    let auth = "hadoop.security.authentication";
+    let simple = builder.clone().conf(auth, "SIMPLE");
+    assert!(simple.simple_auth().unwrap());
+    let kerberos = builder.clone().conf(auth, "kerberos");
+    assert!(!kerberos.simple_auth().unwrap());
+    // An explicit user wins.
+    assert_eq!(
+        builder.user("alice").default_user(Some("bob")).get_user(),
+        Some("alice")
+    );
+
+    let Some((namenode, port)) = cluster_addr() else {
+        return;
+    };
+    // Same as `HADOOP_USER_NAME=hdfs-sys-env`, without racing other tests
+    // on the process env.
+    let fs = ConnectBuilder::new(&namenode)
+        .port(port)
+        .default_user(Some("hdfs-sys-env"))
+        .connect()
+        .expect("connect to test cluster");
+
+    let path = "/tmp/hdfs-sys-test-hadoop-user-name";
+    write_file(&fs, path, b"owner");
+    assert_eq!(fs.metadata(path).unwrap().owner(), "hdfs-sys-env");
+}
+
+#[test]
+fn test_metadata_lossy_owner() {
+    let owner = c"al\xffce";
+    let mut info = unsafe { std::mem::zeroed::<hdfsFileInfo>() };
+    info.mOwner = owner.as_ptr() as *mut _;
+    let meta = unsafe { Metadata::from_raw(&info) };
+    assert_eq!(meta.owner(), "al\u{fffd}ce");
+    assert_eq!(meta.group(), "");
+}
+
+#[test]
+fn test_metadata_modified() {
+    let mut info = unsafe { std::mem::zeroed::<hdfsFileInfo>() };
+    // 2023-11-14T22:13:20Z, in seconds as set by `libhdfs`.
+    info.mLastMod = 1_700_000_000;
+    let meta = unsafe { Metadata::from_raw(&info) };
+    assert_eq!(
+        meta.modified().unwrap(),
+        std::time::UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+    );
+}
+
+#[test]
+fn test_metadata_accessed() {
+    let mut info = unsafe { std::mem::zeroed::<hdfsFileInfo>() };
+    info.mLastAccess = 1_700_000_000;
+    let meta = unsafe { Metadata::from_raw(&info) };
+    assert_eq!(
+        meta.accessed().unwrap(),
+        std::time::UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+    );
+
+    info.mLastAccess = 0;
+    let meta = unsafe { Metadata::from_raw(&info) };
+    assert_eq!(
+        meta.accessed().unwrap_err().kind(),
+        io::ErrorKind::Unsupported
+    );
+
+    info.mLastAccess = -1;
+    let meta = unsafe { Metadata::from_raw(&info) };
+    assert_eq!(
+        meta.accessed().unwrap_err().kind(),
+        io::ErrorKind::InvalidData
+    );
+}
+
+/// A [`PreadFile`] that fails the first `failures` reads.
+struct FlakyFile {
+    data: Vec<u8>,
+    failures: Cell<usize>,
+    reads: Cell<Vec<u64>>,
+}
+
+impl PreadFile for FlakyFile {
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        let mut reads = self.reads.take();
+        reads.push(offset);
+        self.reads.set(reads);
+
+        if self.failures.get() > 0 {
+            self.failures.set(self.failures.get() - 1);
+            return Err(io::Error::other("datanode is gone"));
+        }
+        let data = self.data.get(offset as usize..).unwrap_or_default();
+        let n = data.len().min(buf.len());
+        buf[..n].copy_from_slice(&data[..n]);
+        Ok(n)
+    }
+}
+
+#[test]
+fn test_read_exact_at_default() {
+    let file = FlakyFile {
+        data: b"Hello, World!".to_vec(),
+        failures: Cell::new(0),
+        reads: Cell::new(Vec::new()),
+    };
+
+    let mut buf = [0; 5];
+    file.read_exact_at(7, &mut buf).unwrap();
+    assert_eq!(&buf, b"World");
+
+    let err = file.read_exact_at(10, &mut buf).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn test_read_at_offset_too_large() {
+    let Some(fs) = cluster() else { return };
+
+    let dir = TempDir::new(&fs, "read-at-offset-too-large");
+    let path = &dir.join("file");
+    write_file(&fs, path, b"Hello, World!");
+    let f = fs.open(path).unwrap();
+
+    // Would be a negative offset for `hdfsPread`.
+    let err = f.read_at(u64::MAX, &mut [0; 5]).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn test_read_exact_at() {
+    let Some(fs) = cluster() else { return };
+
+    let dir = TempDir::new(&fs, "read-exact-at");
+    let path = &dir.join("file");
+    write_file(&fs, path, b"Hello, World!");
+    let f = fs.open(path).unwrap();
+
+    let mut buf = [0; 5];
+    f.read_exact_at(7, &mut buf).unwrap();
+    assert_eq!(&buf, b"World");
+    f.read_exact_at(8, &mut buf).unwrap();
+    assert_eq!(&buf, b"orld!");
+    f.read_exact_at(3, &mut []).unwrap();
+
+    let err = f.read_exact_at(10, &mut buf).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    let err = f.read_exact_at(100, &mut buf).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    let err = f.read_exact_at(u64::MAX, &mut buf).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn test_retry_reader() {
+    let data = b"Hello, World!".to_vec();
+    let file = FlakyFile {
+        data: data.clone(),
+        failures: Cell::new(0),
+        reads: Cell::new(Vec::new()),
+    };
+    let reopens = Cell::new(0);
+    let reopen = || {
+        reopens.set(reopens.get() + 1);
+        // The reopened file fails once more before it recovers.
+        Ok(FlakyFile {
+            data: data.clone(),
+            failures: Cell::new(if reopens.get() == 1 { 1 } else { 0 }),
+            reads: Cell::new(Vec::new()),
+        })
+    };
+    let mut r = RetryReader::new(file, reopen).max_retries(2);
+
+    let mut buf = [0; 7];
+    r.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"Hello, ");
+    assert_eq!(reopens.get(), 0);
+
+    // Fail the next read at offset 7.
+    r.get_ref().failures.set(1);
+    let mut rest = Vec::new();
+    r.read_to_end(&mut rest).unwrap();
+    assert_eq!(rest, b"World!");
+    assert_eq!(reopens.get(), 2);
+    assert_eq!(r.offset(), 13);
+    // The retried read must happen at the same offset.
+    assert_eq!(r.into_inner().reads.take()[0], 7);
+}
+
+#[test]
+fn test_retry_reader_gives_up() {
+    let file = || FlakyFile {
+        data: b"Hello, World!".to_vec(),
+        failures: Cell::new(usize::MAX),
+        reads: Cell::new(Vec::new()),
+    };
+    let reopens = Cell::new(0);
+    let mut r = RetryReader::new(file(), || {
+        reopens.set(reopens.get() + 1);
+        Ok(file())
+    })
+    .max_retries(3);
+
+    let err = r.read(&mut [0; 4]).unwrap_err();
+    assert_eq!(err.to_string(), "datanode is gone");
+    assert_eq!(reopens.get(), 3);
+    assert_eq!(r.offset(), 0);
+}
+
+#[test]
+fn test_rename() {
+    let Some(fs) = cluster() else { return };
+
+    let dir = TempDir::new(&fs, "rename");
+    let (from, to) = (&dir.join("from"), &dir.join("to"));
+    write_file(&fs, from, b"Hello, World!");
+    write_file(&fs, to, b"Old");
+
+    let err = fs.rename(from, to, false).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+    assert!(fs.exists(from).unwrap());
+
+    fs.rename(from, to, true).unwrap();
+    assert!(!fs.exists(from).unwrap());
+    assert_eq!(fs.metadata(to).unwrap().len(), 13);
+
+    // Without overwrite to a missing destination.
+    fs.rename(to, from, false).unwrap();
+    assert!(fs.exists(from).unwrap());
+    assert!(!fs.exists(to).unwrap());
+}
+
+#[test]
+fn test_move_file() {
+    let Some(fs) = cluster() else { return };
+
+    let dir = TempDir::new(&fs, "move-file");
+    let staging = &dir.join("staging/file");
+    let done = &dir.join("done");
+    fs.create_dir_all(done).unwrap();
+    fs.create_dir_all(&dir.join("staging")).unwrap();
+    write_file(&fs, staging, b"Hello, World!");
+
+    let err = fs
+        .move_file(staging, &dir.join("missing/file"))
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    // A directory is not replaced or moved into.
+    let err = fs.move_file(staging, done).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+    let err = fs.move_file(done, &dir.join("moved")).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+    let to = &dir.join("done/file");
+    fs.move_file(staging, to).unwrap();
+    assert!(!fs.exists(staging).unwrap());
+    assert_eq!(fs.metadata(to).unwrap().len(), 13);
+
+    let err = fs.move_file(staging, to).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+    write_file(&fs, staging, b"Again");
+    let err = fs.move_file(staging, to).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+    assert_eq!(fs.metadata(to).unwrap().len(), 13);
+}
+
+#[test]
+fn test_create_dir_all() {
+    let Some(fs) = cluster() else { return };
+
+    let dir = TempDir::new(&fs, "create-dir-all");
+    let path = &dir.join("a/b/c");
+    fs.create_dir_all(path).unwrap();
+    assert!(fs.is_dir(path).unwrap());
+
+    // Existing directories are fine.
+    fs.create_dir_all(path).unwrap();
+    fs.create_dir_all(&dir.join("a")).unwrap();
+
+    let file = &dir.join("file");
+    write_file(&fs, file, b"Hello, World!");
+    let err = fs.create_dir_all(file).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+    assert!(fs.create_dir_all(&dir.join("file/sub")).is_err());
+}
+
+#[test]
+fn test_create_with() {
+    let Some(fs) = cluster() else { return };
+
+    let dir = TempDir::new(&fs, "create-with");
+    let path = &dir.join("file");
+    let block_size = 2 * 1024 * 1024;
+    let mut f = fs.create_with(path, 4096, 1, block_size).unwrap();
+    f.write_all(b"Hello, World!").unwrap();
+    f.close().unwrap();
+
+    let meta = fs.metadata(path).unwrap();
+    assert_eq!(meta.block_size(), block_size);
+    assert_eq!(meta.replication(), 1);
+    assert_eq!(meta.len(), 13);
+
+    let err = fs.create_with(path, 0, 0, block_size + 1).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    // It would be a negative `i16` for `libhdfs`.
+    let err = fs.create_with(path, 0, 40000, 0).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn test_create_with_defaults() {
+    let Some(fs) = cluster() else { return };
+
+    let dir = TempDir::new(&fs, "create-with-defaults");
+    let path = &dir.join("default");
+    fs.create_with(path, 0, 0, 0).unwrap().close().unwrap();
+
+    let meta = fs.metadata(path).unwrap();
+    assert_eq!(meta.block_size(), fs.default_block_size(path).unwrap());
+    let replication = Config::get_int("dfs.replication").unwrap().unwrap_or(3);
+    assert_eq!(i32::from(meta.replication()), replication);
+
+    // Replicas beyond the live datanodes are only queued, the file is
+    // still complete once one replica is written.
+    let path = &dir.join("replicated");
+    let mut f = fs.create_with(path, 0, 2, 0).unwrap();
+    f.write_all(b"Hello, World!").unwrap();
+    f.close().unwrap();
+    assert_eq!(fs.metadata(path).unwrap().replication(), 2);
+}
+
+#[test]
+fn test_default_fs() {
+    let Some(fs) = cluster() else { return };
+
+    // `core-default.xml` always sets it, `file:///` without `core-site.xml`.
+    let default_fs = fs.default_fs().unwrap().expect("fs.defaultFS is set");
+    assert!(default_fs.contains("://"), "{default_fs}");
+
+    assert_eq!(Config::get_str("hdfs-sys.test.unset").unwrap(), None);
+}
+
+#[test]
+fn test_config_get_int_in_range() {
+    // Reading config needs the JVM and hadoop jars like a connection.
+    let Some(_fs) = cluster() else { return };
+
+    // `io.file.buffer.size` is set by `core-default.xml`.
+    let key = "io.file.buffer.size";
+    let size = Config::get_int_in_range(key, 1, i32::MAX).unwrap().unwrap();
+    assert_eq!(Config::get_int(key).unwrap(), Some(size));
+
+    let err = Config::get_int_in_range(key, size + 1, i32::MAX).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    let err = Config::get_int_in_range(key, 0, size - 1).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    let err = Config::get_int_in_range(key, 1, 0).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+    let unset = "hdfs-sys.test.unset";
+    assert_eq!(Config::get_int_in_range(unset, 1, 10).unwrap(), None);
+    assert!(Config::get_int("fs.defaultFS").is_err());
+}
+
+#[test]
+fn test_exists_and_is_dir() {
+    let Some(fs) = cluster() else { return };
+
+    let path = "/tmp/hdfs-sys-test-exists";
+    write_file(&fs, path, b"Hello, World!");
+
+    assert!(fs.exists(path).unwrap());
+    assert!(!fs.is_dir(path).unwrap());
+    assert!(fs.exists("/tmp").unwrap());
+    assert!(fs.is_dir("/tmp").unwrap());
+
+    let path = "/tmp/hdfs-sys-test-exists-not-exist";
+    assert!(!fs.exists(path).unwrap());
+    assert!(!fs.is_dir(path).unwrap());
+}
+
+#[test]
+#[cfg(feature = "hdfs_2_9")]
+fn test_append_with() {
+    let Some(fs) = cluster() else { return };
+
+    let path = "/tmp/hdfs-sys-test-append-with";
+    write_file(&fs, path, b"Hello, ");
+
+    for (buffer_size, block_size) in [(4096, 0), (0, 2 * 1024 * 1024)] {
+        let err = fs.append_with(path, buffer_size, block_size).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+    }
+
+    let mut f = fs.append_with(path, 0, 0).unwrap();
+    f.write_all(b"World!").unwrap();
+    f.close().unwrap();
+
+    let mut content = String::new();
+    fs.open(path).unwrap().read_to_string(&mut content).unwrap();
+    assert_eq!(content, "Hello, World!");
+}
+
+#[test]
+fn test_delete_batched() {
+    let Some(fs) = cluster() else { return };
+
+    let path = "/tmp/hdfs-sys-test-delete-batched";
+    for i in 0..25 {
+        write_file(&fs, &format!("{path}/{i}"), b"Hello, World!");
+    }
+    assert_eq!(fs.read_dir(path).unwrap().len(), 25);
+
+    let mut counts = Vec::new();
+    fs.delete_batched(path, 10, |n| counts.push(n)).unwrap();
+    assert_eq!(counts, [10, 20, 25]);
+    assert!(!fs.exists(path).unwrap());
+
+    let err = fs.delete_batched(path, 0, |_| {}).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn test_open_options() {
+    assert_eq!(OpenOptions::new().to_raw().unwrap(), (0, 0, 0, 0));
+    assert_eq!(
+        OpenOptions::new()
+            .write(true)
+            .buffer_size(4096)
+            .replication(3)
+            .block_size(1024 * 1024)
+            .to_raw()
+            .unwrap(),
+        (1, 4096, 3, 1024 * 1024)
+    );
+
+    for opts in [
+        OpenOptions::new().replication(3),
+        OpenOptions::new().block_size(1024 * 1024),
+        OpenOptions::new().buffer_size(u32::MAX),
+        OpenOptions::new().write(true).replication(u16::MAX),
+        OpenOptions::new().write(true).block_size(u64::MAX),
+    ] {
+        let err = opts.to_raw().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
+
+#[test]
+fn test_open_with() {
+    let Some(fs) = cluster() else { return };
+
+    let path = "/tmp/hdfs-sys-test-open-with";
+    for buffer_size in [0, 1024] {
+        let opts = OpenOptions::new().write(true).buffer_size(buffer_size);
+        let mut f = fs.open_with(path, &opts).unwrap();
+        f.write_all(b"Hello, World!").unwrap();
+        f.close().unwrap();
+
+        let opts = OpenOptions::new().buffer_size(buffer_size);
+        let mut content = String::new();
+        let mut f = fs.open_with(path, &opts).unwrap();
+        f.read_to_string(&mut content).unwrap();
+        assert_eq!(content, "Hello, World!");
+    }
+}
+
+#[test]
+fn test_set_replication_and_wait() {
+    let Some(fs) = cluster() else { return };
+
+    let path = "/tmp/hdfs-sys-test-set-replication-and-wait";
+    let opts = OpenOptions::new().write(true).replication(3);
+    let mut f = fs.open_with(path, &opts).unwrap();
+    f.write_all(b"Hello, World!").unwrap();
+    f.close().unwrap();
+
+    fs.set_replication_and_wait(path, 1, Duration::from_secs(60))
+        .unwrap();
+    assert_eq!(fs.metadata(path).unwrap().replication(), 1);
+    let hosts = fs.block_hosts(path, 0, 13).unwrap();
+    assert!(hosts.iter().all(|v| v.len() == 1));
+}
+
+#[test]
+fn test_stream_position() {
+    let Some(fs) = cluster() else { return };
+
+    let path = "/tmp/hdfs-sys-test-stream-position";
+    let mut f = fs.create(path).unwrap();
+    assert_eq!(f.stream_position().unwrap(), 0);
+    f.write_all(b"Hello, ").unwrap();
+    assert_eq!(f.stream_position().unwrap(), 7);
+    f.write_all(b"World!").unwrap();
+    assert_eq!(f.stream_position().unwrap(), 13);
+    f.close().unwrap();
+
+    let mut f = fs.open(path).unwrap();
+    assert_eq!(f.stream_position().unwrap(), 0);
+    f.read_exact(&mut [0; 5]).unwrap();
+    assert_eq!(f.stream_position().unwrap(), 5);
+    f.seek(SeekFrom::End(-1)).unwrap();
+    assert_eq!(f.stream_position().unwrap(), 12);
+}
+
+#[test]
+fn test_read_dir_files_dirs() {
+    let Some(fs) = cluster() else { return };
+
+    let path = "/tmp/hdfs-sys-test-read-dir-files-dirs";
+    for file in ["a", "b", "c", "d/e", "f/g"] {
+        write_file(&fs, &format!("{path}/{file}"), b"Hello, World!");
+    }
+
+    assert_eq!(fs.read_dir(path).unwrap().len(), 5);
+    assert_eq!(fs.read_dir(path).unwrap().files().count(), 3);
+    let dirs: Vec<_> = fs.read_dir(path).unwrap().dirs().collect();
+    assert_eq!(dirs.len(), 2);
+    assert!(dirs.iter().all(|entry| entry.is_dir()));
+}
+
+#[test]
+fn test_read_dir_filters() {
+    let entry = |is_dir| {
+        let mut info = unsafe { std::mem::zeroed::<hdfsFileInfo>() };
+        if is_dir {
+            info.mKind = tObjectKind_kObjectKindDirectory;
+        } else {
+            info.mKind = tObjectKind_kObjectKindFile;
+        }
+        unsafe { Metadata::from_raw(&info) }
+    };
+    let entries = || ReadDir::new(vec![entry(false), entry(true), entry(false)]);
+
+    assert_eq!(entries().len(), 3);
+    assert_eq!(entries().files().count(), 2);
+    assert_eq!(entries().dirs().count(), 1);
+}
+
+#[test]
+fn test_walk() {
+    let Some(fs) = cluster() else { return };
+
+    let path = "/tmp/hdfs-sys-test-walk";
+    for file in ["a", "b/c", "b/d/e", "f/g"] {
+        write_file(&fs, &format!("{path}/{file}"), b"Hello, World!");
+    }
+
+    let mut paths = Vec::new();
+    for entry in fs.walk(path) {
+        let entry = entry.unwrap();
+        // Paths are fully qualified with the namenode uri.
+        let (_, rel) = entry.path().split_once(path).unwrap();
+        paths.push(rel.to_string());
+    }
+    paths.sort();
+    assert_eq!(paths, ["/a", "/b", "/b/c", "/b/d", "/b/d/e", "/f", "/f/g"]);
+
+    let err = fs.walk("/tmp/hdfs-sys-test-walk-not-exist").next().unwrap();
+    assert_eq!(err.unwrap_err().kind(), io::ErrorKind::NotFound);
+
+    fs.delete_batched(path, 10, |_| {}).unwrap();
+}
+
+#[test]
+#[cfg(feature = "hdfs_2_6")]
+fn test_is_encrypted() {
+    let Some(fs) = cluster() else { return };
+
+    let path = "/tmp/hdfs-sys-test-is-encrypted";
+    write_file(&fs, path, b"Hello, World!");
+    assert!(!fs.is_encrypted(path).unwrap());
+
+    // Set `HDFS_TEST_ENCRYPTION_ZONE` to an existing encryption zone to check it.
+    if let Ok(zone) = env::var("HDFS_TEST_ENCRYPTION_ZONE") {
+        let path = format!("{zone}/hdfs-sys-test-is-encrypted");
+        write_file(&fs, &path, b"Hello, World!");
+        assert!(fs.is_encrypted(&path).unwrap());
+    }
+
+    let err = fs.is_encrypted("/tmp/hdfs-sys-test-not-exist").unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+}
+
+#[test]
+fn test_copy_file() {
+    let Some(fs) = cluster() else { return };
+
+    let dir = TempDir::new(&fs, "copy-file");
+    let (src, dst) = (&dir.join("src"), &dir.join("dst"));
+    let data: Vec<u8> = (0..10_000).map(|i| i as u8).collect();
+    write_file(&fs, src, &data);
+
+    let mut progress = Vec::new();
+    let n = fs.copy_file(src, dst, 4096, |n| progress.push(n)).unwrap();
+    assert_eq!(n, 10_000);
+    assert_eq!(progress.last(), Some(&10_000));
+    assert!(progress.len() >= 3, "{progress:?}");
+    assert!(progress.windows(2).all(|w| w[0] < w[1]), "{progress:?}");
+
+    let mut copied = Vec::new();
+    fs.open(dst).unwrap().read_to_end(&mut copied).unwrap();
+    assert_eq!(copied, data);
+
+    let err = fs.copy_file(src, dst, 0, |_| {}).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[cfg(feature = "jni-interop")]
+#[test]
+fn test_current_jni_env() {
+    use super::current_jni_env;
+
+    // The JVM is running after connecting.
+    let Some(_fs) = cluster() else { return };
+
+    let env = current_jni_env();
+    assert!(!env.is_null());
+    assert_eq!(current_jni_env(), env);
+
+    // Each thread is attached with an env of its own.
+    let other = std::thread::spawn(|| current_jni_env() as usize)
+        .join()
+        .unwrap();
+    assert_ne!(other, 0);
+    assert_ne!(other, env as usize);
+}
+
+#[cfg(all(feature = "jni-interop", feature = "hdfs_3_0"))]
+#[test]
+fn test_detach_current_thread() {
+    use super::{current_jni_env, detach_current_thread};
+
+    // A thread never attached by `libhdfs` is a no-op.
+    std::thread::spawn(|| {
+        detach_current_thread().unwrap();
+        detach_current_thread().unwrap();
+    })
+    .join()
+    .unwrap();
+
+    let Some(fs) = cluster() else { return };
+
+    std::thread::spawn(move || {
+        assert!(!current_jni_env().is_null());
+        fs.check().unwrap();
+        detach_current_thread().unwrap();
+        detach_current_thread().unwrap();
+
+        // Attached again by the next call, and detached on exit as usual.
+        fs.check().unwrap();
+        assert!(!current_jni_env().is_null());
+    })
+    .join()
+    .unwrap();
+}
+
+#[test]
+fn test_open_prefetch() {
+    let Some(fs) = cluster() else { return };
+
+    let dir = TempDir::new(&fs, "open-prefetch");
+    let path = &dir.join("file");
+    let data: Vec<u8> = (0..100_000).map(|i| i as u8).collect();
+    write_file(&fs, path, &data);
+
+    let mut f = fs.open_prefetch(path).unwrap();
+    assert_eq!(f.stream_position().unwrap(), 0);
+    let mut read = Vec::new();
+    f.read_to_end(&mut read).unwrap();
+    assert_eq!(read, data);
+
+    // Files shorter than the prefetch work the same.
+    let short = &dir.join("short");
+    write_file(&fs, short, b"Hello, World!");
+    let mut read = String::new();
+    fs.open_prefetch(short)
+        .unwrap()
+        .read_to_string(&mut read)
+        .unwrap();
+    assert_eq!(read, "Hello, World!");
+}
+
+#[test]
+fn test_put_and_get() {
+    let Some(fs) = cluster() else { return };
+
+    let dir = env::temp_dir();
+    let local = dir.join("hdfs-sys-test-put");
+    let data: Vec<u8> = (0..10_000).map(|i| i as u8).collect();
+    std::fs::write(&local, &data).unwrap();
+
+    let path = "/tmp/hdfs-sys-test-put-and-get";
+    // A buffer size not dividing the length exercises the short last read.
+    assert_eq!(fs.put(&local, path, 4096).unwrap(), data.len() as u64);
+    assert_eq!(fs.metadata(path).unwrap().len(), data.len() as u64);
+
+    let local = dir.join("hdfs-sys-test-get");
+    assert_eq!(fs.get(path, &local, 3000).unwrap(), data.len() as u64);
+    assert_eq!(std::fs::read(&local).unwrap(), data);
+
+    let err = fs.get(path, &local, 0).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn test_connect_default() {
+    // The test cluster must be `fs.defaultFS` in the config on `CLASSPATH`.
+    let Some(_) = cluster() else { return };
+
+    let fs = Filesystem::connect_default().unwrap();
+    assert!(fs.exists("/").unwrap());
+}
+
+#[test]
+fn test_check() {
+    let Some(fs) = cluster() else { return };
+
+    fs.check().unwrap();
+    fs.clone().check().unwrap();
+}
+
+#[test]
+fn test_default_block_size() {
+    let Some(fs) = cluster() else { return };
+
+    assert!(fs.default_block_size("/tmp").unwrap() > 0);
+    assert!(
+        fs.default_block_size("/tmp/hdfs-sys-test-not-exist")
+            .unwrap()
+            > 0
+    );
+}
+
+#[test]
+fn test_read_vectored() {
+    let Some(fs) = cluster() else { return };
+
+    let path = "/tmp/hdfs-sys-test-read-vectored";
+    write_file(&fs, path, b"Hello, World!");
+
+    let mut f = fs.open(path).unwrap();
+    let (mut a, mut b, mut c) = ([0; 5], [0; 0], [0; 4]);
+    let mut bufs = [
+        io::IoSliceMut::new(&mut a),
+        io::IoSliceMut::new(&mut b),
+        io::IoSliceMut::new(&mut c),
+    ];
+    assert_eq!(f.read_vectored(&mut bufs).unwrap(), 9);
+    assert_eq!(&a, b"Hello");
+    assert_eq!(&c, b", Wo");
+
+    // Stops at the short read at EOF.
+    let (mut a, mut b) = ([0; 8], [0; 8]);
+    let mut bufs = [io::IoSliceMut::new(&mut a), io::IoSliceMut::new(&mut b)];
+    assert_eq!(f.read_vectored(&mut bufs).unwrap(), 4);
+    assert_eq!(&a[..4], b"rld!");
+    assert_eq!(b, [0; 8]);
+}
+
+#[test]
+#[cfg(feature = "hdfs_2_7")]
+fn test_truncate() {
+    let Some(fs) = cluster() else { return };
+
+    let path = "/tmp/hdfs-sys-test-truncate";
+    write_file(&fs, path, b"Hello, World!");
+
+    // Truncating to 0 is always on a block boundary.
+    assert_eq!(fs.truncate(path, 0).unwrap(), TruncateResult::Completed);
+    assert_eq!(fs.metadata(path).unwrap().len(), 0);
+
+    write_file(&fs, path, b"Hello, World!");
+    if fs.truncate(path, 5).unwrap() == TruncateResult::InProgress {
+        let deadline = std::time::Instant::now() + Duration::from_secs(60);
+        while fs.metadata(path).unwrap().len() != 5 {
+            assert!(std::time::Instant::now() < deadline, "truncate timed out");
+            std::thread::sleep(Duration::from_millis(100));
+        }
+    }
+    let mut content = String::new();
+    fs.open(path).unwrap().read_to_string(&mut content).unwrap();
+    assert_eq!(content, "Hello");
+}
+
+#[test]
+fn test_checked_size() {
+    use super::fs::checked_size;
+
+    assert_eq!(checked_size(0).unwrap(), 0);
+    assert_eq!(checked_size(i64::MAX).unwrap(), i64::MAX as u64);
+    // The error sentinel never turns into `u64::MAX`.
+    assert!(checked_size(-1).is_err());
+    assert_eq!(
+        checked_size(-2).unwrap_err().kind(),
+        io::ErrorKind::InvalidData
+    );
+}
+
+#[test]
+fn test_block_size_histogram() {
+    let Some(fs) = cluster() else { return };
+
+    let dir = TempDir::new(&fs, "block-size-histogram");
+    for name in ["a", "b"] {
+        write_file_with_block_size(&fs, &dir.join(name), b"Hello", 1024 * 1024);
+    }
+    write_file_with_block_size(&fs, &dir.join("c"), b"Hello", 2 * 1024 * 1024);
+    fs.create_dir_all(&dir.join("sub")).unwrap();
+    write_file_with_block_size(&fs, &dir.join("sub/d"), b"Hello", 1024 * 1024);
+
+    let histogram = fs.block_size_histogram(&dir.path).unwrap();
+    assert_eq!(
+        histogram.into_iter().collect::<Vec<_>>(),
+        [(1024 * 1024, 2), (2 * 1024 * 1024, 1)]
+    );
+}
+
+#[test]
+fn test_stats() {
+    let Some(fs) = cluster() else { return };
+
+    let stats = fs.stats().unwrap();
+    assert!(stats.capacity > 0);
+    assert!(stats.capacity >= stats.used);
+    assert_eq!(stats.remaining, stats.capacity - stats.used);
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn test_async_read_dir() {
+    use futures_core::Stream;
+    use std::pin::Pin;
+
+    let Some(fs) = cluster() else { return };
+
+    let path = "/tmp/hdfs-sys-test-async-read-dir";
+    for i in 0..3 {
+        write_file(&fs, &format!("{path}/{i}"), b"Hello, World!");
+    }
+
+    let fs = AsyncFilesystem::new(fs);
+    let mut stream = fs.read_dir(path);
+    let mut names = Vec::new();
+    while let Some(entry) = std::future::poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await {
+        let entry = entry.unwrap();
+        names.push(entry.path().rsplit('/').next().unwrap().to_string());
+    }
+    names.sort();
+    assert_eq!(names, ["0", "1", "2"]);
+
+    let mut stream = fs.read_dir("/tmp/hdfs-sys-test-async-read-dir-not-exist");
+    let err = std::future::poll_fn(|cx| Pin::new(&mut stream).poll_next(cx))
+        .await
+        .unwrap()
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    assert!(
+        std::future::poll_fn(|cx| Pin::new(&mut stream).poll_next(cx))
+            .await
+            .is_none()
+    );
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn test_async_read_cancellable() {
+    use tokio_util::sync::CancellationToken;
+
+    /// Cancels `token` during the `cancel_at`th read, `0` means never.
+    struct CancelOnRead {
+        token: CancellationToken,
+        cancel_at: usize,
+        reads: usize,
+    }
+
+    impl Read for CancelOnRead {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.reads += 1;
+            if self.reads == self.cancel_at {
+                self.token.cancel();
+            }
+            buf.fill(b'x');
+            Ok(buf.len())
+        }
+    }
+
+    let token = CancellationToken::new();
+    let mut r = CancelOnRead {
+        token: token.clone(),
+        cancel_at: 0,
+        reads: 0,
+    };
+    let buf = async_fs::read_chunks(&mut r, 10, 4, &token).unwrap();
+    assert_eq!(buf, b"xxxxxxxxxx");
+    assert_eq!(r.reads, 3);
+
+    let token = CancellationToken::new();
+    let mut r = CancelOnRead {
+        token: token.clone(),
+        cancel_at: 2,
+        reads: 0,
+    };
+    let (res, reads) = tokio::task::spawn_blocking(move || {
+        let res = async_fs::read_chunks(&mut r, 1 << 20, 4, &token);
+        (res, r.reads)
+    })
+    .await
+    .unwrap();
+    assert_eq!(res.unwrap_err().kind(), io::ErrorKind::Interrupted);
+    // The chunk in flight when cancelled finishes, no more is read.
+    assert_eq!(reads, 2);
+
+    let Some(fs) = cluster() else { return };
+    let dir = TempDir::new(&fs, "async-read-cancellable");
+    let path = &dir.join("file");
+    write_file(&fs, path, &[b'x'; 1024]);
+
+    let fs = AsyncFilesystem::new(fs);
+    let f = fs.open(path).await.unwrap();
+    let token = CancellationToken::new();
+    assert_eq!(
+        f.read_cancellable(100, 16, &token).await.unwrap().len(),
+        100
+    );
+    token.cancel();
+    let err = f.read_cancellable(100, 16, &token).await.unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::Interrupted);
+    assert_eq!(f.read(10).await.unwrap().len(), 10);
+}
@@ -0,0 +1,56 @@
+use std::io;
+
+use super::{Filesystem, Metadata, ReadDir};
+
+/// A depth-first iterator over a directory tree, returned by
+/// [`Filesystem::walk`].
+///
+/// Each directory is listed with a single `hdfsListDirectory` once the
+/// iterator reaches it, the info array is freed right after copied into
+/// [`Metadata`]. Only the entries of the directories along the current path
+/// are kept in memory.
+#[derive(Debug)]
+pub struct Walk {
+    fs: Filesystem,
+    /// Directory to list before yielding the next entry.
+    pending: Option<String>,
+    stack: Vec<ReadDir>,
+}
+
+impl Walk {
+    pub(crate) fn new(fs: Filesystem, path: &str) -> Walk {
+        Walk {
+            fs,
+            pending: Some(path.to_string()),
+            stack: Vec::new(),
+        }
+    }
+}
+
+impl Iterator for Walk {
+    type Item = io::Result<Metadata>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(path) = self.pending.take() {
+            match self.fs.read_dir(&path) {
+                Ok(entries) => self.stack.push(entries),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+
+        loop {
+            let entries = self.stack.last_mut()?;
+            match entries.next() {
+                Some(entry) => {
+                    if entry.is_dir() {
+                        self.pending = Some(entry.path().to_string());
+                    }
+                    return Some(Ok(entry));
+                }
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use std::ffi::CStr;
+use std::io;
+use std::marker::PhantomData;
+use std::os::raw::c_int;
+use std::slice;
+
+use crate::*;
+
+use super::File;
+
+/// A java `ByteBufferPool` class, used by zero-copy reads for the fallback copy.
+///
+/// The pool is referred by the class name in JNI format, like
+/// `org/apache/hadoop/io/ElasticByteBufferPool`. `libhdfs` creates a new
+/// instance of the class while building [`RzOptions`], so a wrong class name
+/// fails [`RzOptionsBuilder::build`] with `EINVAL`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteBufferPool<'a> {
+    class: &'a CStr,
+}
+
+impl<'a> ByteBufferPool<'a> {
+    /// `ElasticByteBufferPool`, the pool used by default.
+    pub const ELASTIC: ByteBufferPool<'static> = ByteBufferPool {
+        class: match CStr::from_bytes_with_nul(ELASTIC_BYTE_BUFFER_POOL_CLASS) {
+            Ok(class) => class,
+            Err(_) => unreachable!(),
+        },
+    };
+
+    /// `WeakReferencedElasticByteBufferPool`, which lets GC reclaim idle buffers.
+    ///
+    /// This class is only available in hadoop 3.3.5 or later releases.
+    pub const WEAK_REFERENCED_ELASTIC: ByteBufferPool<'static> = ByteBufferPool {
+        class: c"org/apache/hadoop/io/WeakReferencedElasticByteBufferPool",
+    };
+
+    /// Use the pool of `class`, which must implement `org.apache.hadoop.io.ByteBufferPool`
+    /// and have a public constructor without arguments.
+    pub const fn new(class: &'a CStr) -> Self {
+        ByteBufferPool { class }
+    }
+
+    /// Class name of this pool.
+    pub fn class(&self) -> &'a CStr {
+        self.class
+    }
+}
+
+/// Options used by [`File::read_zero_copy_with`], freed on drop.
+///
+/// Build one with [`RzOptionsBuilder`].
+#[derive(Debug)]
+pub struct RzOptions {
+    opts: *mut hadoopRzOptions,
+}
+
+impl RzOptions {
+    /// Get the raw `hadoopRzOptions` handle of these options.
+    pub fn as_raw(&self) -> *mut hadoopRzOptions {
+        self.opts
+    }
+}
+
+impl Drop for RzOptions {
+    fn drop(&mut self) {
+        unsafe { hadoopRzOptionsFree(self.opts) }
+    }
+}
+
+/// Builder of [`RzOptions`].
+///
+/// By default, checksums are verified and [`ByteBufferPool::ELASTIC`] is
+/// used for the fallback copy.
+#[derive(Debug, Clone)]
+pub struct RzOptionsBuilder {
+    skip_checksum: bool,
+    pool: Option<ByteBufferPool<'static>>,
+}
+
+impl Default for RzOptionsBuilder {
+    fn default() -> Self {
+        RzOptionsBuilder {
+            skip_checksum: false,
+            pool: Some(ByteBufferPool::ELASTIC),
+        }
+    }
+}
+
+impl RzOptionsBuilder {
+    /// Create a new builder with default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the pool used for the fallback copy.
+    ///
+    /// With `None`, reads that can't be served by zero-copy fail with
+    /// `EPROTONOSUPPORT` instead of falling back.
+    pub fn byte_buffer_pool(&mut self, pool: Option<ByteBufferPool<'static>>) -> &mut Self {
+        self.pool = pool;
+        self
+    }
+
+    /// Skip checksums where possible.
+    ///
+    /// Zero-copy reads of mmapped blocks are only possible when checksums
+    /// are skipped or the block is already verified by the datanode.
+    pub fn skip_checksum(&mut self, skip: bool) -> &mut Self {
+        self.skip_checksum = skip;
+        self
+    }
+
+    /// Allocate the options.
+    pub fn build(&self) -> io::Result<RzOptions> {
+        let opts = unsafe { hadoopRzOptionsAlloc() };
+        if opts.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        // Make sure opts will be freed on error.
+        let opts = RzOptions { opts };
+
+        if unsafe { hadoopRzOptionsSetSkipChecksum(opts.opts, self.skip_checksum as c_int) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        if let Some(pool) = self.pool {
+            let class = pool.class().as_ptr();
+            if unsafe { hadoopRzOptionsSetByteBufferPool(opts.opts, class) } == -1 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        Ok(opts)
+    }
+}
+
+/// A buffer returned by [`File::read_zero_copy`], freed on drop.
+///
+/// The buffer borrows the file it was read from, since `libhdfs` needs the
+/// same stream to release it.
+#[derive(Debug)]
+pub struct ZeroCopyBuffer<'a> {
+    file: hdfsFile,
+    buf: *mut hadoopRzBuffer,
+    _file: PhantomData<&'a File>,
+}
+
+impl ZeroCopyBuffer<'_> {
+    /// Data of this buffer, empty if EOF has been reached.
+    pub fn as_slice(&self) -> &[u8] {
+        unsafe {
+            let ptr = hadoopRzBufferGet(self.buf);
+            if ptr.is_null() {
+                return &[];
+            }
+            let len = hadoopRzBufferLength(self.buf);
+            slice::from_raw_parts(ptr.cast(), len as usize)
+        }
+    }
+
+    /// Length of this buffer.
+    pub fn len(&self) -> usize {
+        self.as_slice().len()
+    }
+
+    /// Whether this buffer is empty, which means EOF has been reached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Drop for ZeroCopyBuffer<'_> {
+    fn drop(&mut self) {
+        unsafe { hadoopRzBufferFree(self.file, self.buf) }
+    }
+}
+
+impl File {
+    /// Read up to `max_len` bytes without copying, with default options.
+    ///
+    /// See [`File::read_zero_copy_with`] for details.
+    pub fn read_zero_copy(&mut self, max_len: usize) -> io::Result<ZeroCopyBuffer<'_>> {
+        let opts = RzOptionsBuilder::new().build()?;
+        self.read_zero_copy_with(&opts, max_len)
+    }
+
+    /// Read up to `max_len` bytes without copying.
+    ///
+    /// Zero-copy is only possible for short-circuit local reads of mmapped
+    /// blocks. Otherwise `libhdfs` falls back to copying the data into a
+    /// buffer taken from the byte buffer pool of `opts`.
+    ///
+    /// An empty buffer is returned once EOF has been reached.
+    pub fn read_zero_copy_with(
+        &mut self,
+        opts: &RzOptions,
+        max_len: usize,
+    ) -> io::Result<ZeroCopyBuffer<'_>> {
+        let len = max_len.min(i32::MAX as usize) as i32;
+
+        let buf = unsafe { hadoopReadZero(self.file, opts.opts, len) };
+        if buf.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(ZeroCopyBuffer {
+            file: self.file,
+            buf,
+            _file: PhantomData,
+        })
+    }
+}
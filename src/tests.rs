@@ -1,5 +1,61 @@
 use super::*;
 
+#[test]
+fn test_linked_version() {
+    let version = linked_version();
+    assert!(
+        version == "system" || version.split('.').all(|v| v.parse::<u8>().is_ok()),
+        "unexpected version {version}"
+    );
+}
+
+#[test]
+fn test_build_sources() {
+    use crate::build_sources::{header, libhdfs_sources, parse_version};
+
+    assert_eq!(parse_version("hdfs_2_10"), (2, 10));
+    assert!(parse_version("hdfs_2_10") > parse_version("hdfs_2_9"));
+
+    let has = |version, windows, file: &str| {
+        libhdfs_sources(version, windows)
+            .files
+            .contains(&format!("libhdfs/{version}/{file}"))
+    };
+
+    // Before 2.6, there is no os layer or htable.
+    assert!(!has("hdfs_2_5", false, "os/posix/mutexes.c"));
+    assert!(!has("hdfs_2_5", false, "common/htable.c"));
+
+    // htable is built from 2.6 until it's removed in 3.3.
+    for version in ["hdfs_2_6", "hdfs_2_10", "hdfs_3_2"] {
+        assert!(has(version, false, "common/htable.c"), "{version}");
+        assert!(has(version, true, "common/htable.c"), "{version}");
+    }
+    assert!(!has("hdfs_3_3", false, "common/htable.c"));
+    assert!(!has("hdfs_3_3", true, "common/htable.c"));
+    assert!(has("hdfs_3_3", false, "jclasses.c"));
+
+    // Windows builds the os layer of its own.
+    assert!(has("hdfs_2_6", true, "os/windows/mutexes.c"));
+    assert!(!has("hdfs_2_6", true, "os/posix/mutexes.c"));
+
+    let includes = libhdfs_sources("hdfs_3_3", true).includes;
+    assert!(includes.contains(&"libdirent/include".to_string()));
+    assert!(includes.contains(&"libhdfs/hdfs_3_3/include".to_string()));
+
+    // Every file exists in the vendored sources.
+    for version in ["hdfs_2_2", "hdfs_2_6", "hdfs_2_8", "hdfs_3_2", "hdfs_3_3"] {
+        for windows in [false, true] {
+            for file in libhdfs_sources(version, windows).files {
+                let path = concat!(env!("CARGO_MANIFEST_DIR"), "/").to_string() + &file;
+                assert!(std::path::Path::new(&path).exists(), "{path}");
+            }
+        }
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/").to_string() + &header(version);
+        assert!(std::path::Path::new(&path).exists(), "{path}");
+    }
+}
+
 #[test]
 #[cfg(feature = "hdfs_2_2")]
 fn test_hdfs_abi_2_2() {
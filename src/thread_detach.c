@@ -0,0 +1,39 @@
+/*
+ * Detach the current thread from the JVM of the vendored `libhdfs`.
+ *
+ * Built into the vendored sources of 3.0 or later by the `jni-interop`
+ * feature, for `safe::detach_current_thread`. `libhdfs` attaches a thread on
+ * its first call and only detaches it in the destructor of its thread local
+ * state when the thread exits. Detaching with plain JNI would leave that
+ * state pointing to the freed `JNIEnv`, so the state is cleared first, and
+ * the next call into `libhdfs` attaches the thread again.
+ *
+ * This relies on the vendored build not defining `HAVE_BETTER_TLS`, which
+ * would cache the state in a `__thread` variable private to `getJNIEnv`.
+ */
+
+#include "os/mutexes.h"
+#include "os/thread_local_storage.h"
+
+int hdfs_sys_detach_current_thread(void) {
+    struct ThreadLocalState *state = NULL;
+    int ret;
+
+    /* `threadLocalStorageGet` creates the key on first use under it. */
+    mutexLock(&jvmMutex);
+    ret = threadLocalStorageGet(&state);
+    if (!ret && state) {
+        /* Clearing an existing slot doesn't allocate, so it can't fail. */
+        ret = threadLocalStorageSet(NULL);
+    }
+    mutexUnlock(&jvmMutex);
+    if (ret) {
+        return ret;
+    }
+
+    /* Detaches the thread and frees the exception strings. */
+    if (state) {
+        hdfsThreadDestructor(state);
+    }
+    return 0;
+}